@@ -1,11 +1,11 @@
 use anyhow::Result;
 use axum::{
     Router,
-    extract::{Path, Query, State},
+    extract::{DefaultBodyLimit, Path, Query, State},
     body::Body,
-    http::{HeaderValue, Method, Request, StatusCode, header::HeaderName},
+    http::{HeaderMap, HeaderValue, Method, Request, StatusCode, header::{ACCEPT, HeaderName}},
     response::Json,
-    routing::{delete, get},
+    routing::{delete, get, post},
 };
 use sentry::integrations::tower::{NewSentryLayer, SentryHttpLayer};
 use std::{sync::Arc, time::Duration};
@@ -19,58 +19,38 @@ use tower_http::{
 };
 
 use crate::auth::{AuthConfig, selective_auth_middleware};
+use crate::catalog;
 use crate::constants::FULL_VERSION;
-use crate::interfaces::{AppError, QueryParams, QueryResponse};
-use crate::query;
+use crate::interfaces::{
+    AppError, ArrowFormat, AsyncResultParams, CatalogObject, CatalogParams, PrepareParams, QueryParams, QueryResponse,
+    SessionParams, SubmitOptions, UploadParams,
+};
+use crate::query::{self, PreparedStatementInfo, StatusResponse};
+use crate::session::{self, SessionInfo};
 use crate::state::AppState;
-use serde::Serialize;
-
-#[derive(Serialize)]
-struct PoolStatusResponse {
-    id: String,
-    db_path: String,
-    pool_size: usize,
-    access_mode: String,
-    in_use: usize,
-    idle: usize,
-    total: usize,
-    timeout: Duration,
-}
-
-#[derive(Serialize)]
-struct PoolStatusError {
-    id: String,
-    error: String,
-}
+use crate::upload::{self, UploadInfo};
+use crate::ws;
 
-#[derive(Serialize)]
-#[serde(untagged)]
-enum PoolStatusResult {
-    Success(PoolStatusResponse),
-    Error(PoolStatusError),
-}
-
-#[derive(Serialize)]
-struct QueryStatus {
-    id: String,
-    database: String,
-    sql: String,
-    started_at: String,
-}
+/// Picks the Arrow IPC file format from the Accept header unless the request names a format.
+fn negotiate_arrow_format(arrow_format: &mut Option<ArrowFormat>, headers: &HeaderMap) {
+    let accepts_file = headers
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains(ArrowFormat::File.content_type()));
 
-#[derive(Serialize)]
-struct StatusResponse {
-    pools: Vec<PoolStatusResult>,
-    total_pools: usize,
-    running_queries: Vec<QueryStatus>,
-    total_running_queries: usize,
+    if arrow_format.is_none() && accepts_file {
+        *arrow_format = Some(ArrowFormat::File);
+    }
 }
 
 #[axum::debug_handler]
 async fn handle_get(
     State(app_state): State<Arc<AppState>>,
-    Query(params): Query<QueryParams>,
+    headers: HeaderMap,
+    Query(mut params): Query<QueryParams>,
 ) -> Result<QueryResponse, AppError> {
+    negotiate_arrow_format(&mut params.arrow_format, &headers);
+
     let res = query::with_db_retry(&app_state, &params, |state, params| {
         Box::pin(query::handle(state, params))
     })
@@ -82,8 +62,16 @@ async fn handle_get(
 #[axum::debug_handler]
 async fn handle_post(
     State(app_state): State<Arc<AppState>>,
-    Json(params): Json<QueryParams>,
+    headers: HeaderMap,
+    Query(options): Query<SubmitOptions>,
+    Json(mut params): Json<QueryParams>,
 ) -> Result<QueryResponse, AppError> {
+    negotiate_arrow_format(&mut params.arrow_format, &headers);
+
+    if options.run_async {
+        return query::submit(&app_state, params).await;
+    }
+
     let res = query::with_db_retry(&app_state, &params, |state, params| {
         Box::pin(query::handle(state, params))
     })
@@ -94,55 +82,7 @@ async fn handle_post(
 
 #[axum::debug_handler]
 async fn status_handler(State(app_state): State<Arc<AppState>>) -> Result<Json<StatusResponse>, AppError> {
-    let states = app_state.states.lock().await;
-    let mut pool_statuses = Vec::new();
-
-    for (id, db_state) in states.iter() {
-        match db_state.db.status() {
-            Ok(pool_status) => {
-                pool_statuses.push(PoolStatusResult::Success(PoolStatusResponse {
-                    id: id.clone(),
-                    db_path: pool_status.db_path,
-                    pool_size: pool_status.pool_size,
-                    access_mode: pool_status.access_mode,
-                    in_use: pool_status.in_use,
-                    idle: pool_status.idle,
-                    total: pool_status.total,
-                    timeout: pool_status.timeout,
-                }));
-            }
-            Err(error) => {
-                pool_statuses.push(PoolStatusResult::Error(PoolStatusError {
-                    id: id.clone(),
-                    error: error.to_string(),
-                }));
-            }
-        }
-    }
-
-    let running_queries = app_state.get_running_queries().await;
-    let query_statuses: Vec<QueryStatus> = running_queries
-        .into_iter()
-        .map(|query| QueryStatus {
-            id: query.id,
-            database: query.database,
-            sql: query.sql,
-            started_at: query.started_at
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_secs()
-                .to_string(),
-        })
-        .collect();
-
-    let total_running_queries = query_statuses.len();
-
-    Ok(Json(StatusResponse {
-        pools: pool_statuses,
-        total_pools: states.len(),
-        running_queries: query_statuses,
-        total_running_queries,
-    }))
+    Ok(Json(query::status(&app_state).await?))
 }
 
 async fn readiness_probe() -> &'static str {
@@ -162,6 +102,92 @@ async fn cancel_query_handler(
     query::cancel_query(&app_state, query_id).await
 }
 
+#[axum::debug_handler]
+async fn async_query_status_handler(
+    State(app_state): State<Arc<AppState>>,
+    Path(query_id): Path<String>,
+) -> Result<QueryResponse, AppError> {
+    query::async_query_status(&app_state, &query_id).await
+}
+
+#[axum::debug_handler]
+async fn async_query_result_handler(
+    State(app_state): State<Arc<AppState>>,
+    Path(query_id): Path<String>,
+    headers: HeaderMap,
+    Query(mut options): Query<AsyncResultParams>,
+) -> Result<QueryResponse, AppError> {
+    negotiate_arrow_format(&mut options.arrow_format, &headers);
+    query::async_query_result(&app_state, &query_id, options).await
+}
+
+#[axum::debug_handler]
+async fn prepare_handler(
+    State(app_state): State<Arc<AppState>>,
+    Json(params): Json<PrepareParams>,
+) -> Result<Json<PreparedStatementInfo>, AppError> {
+    Ok(Json(query::prepare(&app_state, params).await?))
+}
+
+#[axum::debug_handler]
+async fn execute_prepared_handler(
+    State(app_state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    Json(mut params): Json<QueryParams>,
+) -> Result<QueryResponse, AppError> {
+    negotiate_arrow_format(&mut params.arrow_format, &headers);
+    query::execute_prepared(&app_state, &name, params).await
+}
+
+#[axum::debug_handler]
+async fn open_session_handler(
+    State(app_state): State<Arc<AppState>>,
+    Json(params): Json<SessionParams>,
+) -> Result<Json<SessionInfo>, AppError> {
+    Ok(Json(session::open(&app_state, params).await?))
+}
+
+#[axum::debug_handler]
+async fn close_session_handler(
+    State(app_state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+) -> Result<QueryResponse, AppError> {
+    session::close(&app_state, &session_id).await
+}
+
+#[axum::debug_handler]
+async fn catalogs_handler(
+    State(app_state): State<Arc<AppState>>,
+    Path(database): Path<String>,
+    headers: HeaderMap,
+    Query(mut params): Query<CatalogParams>,
+) -> Result<QueryResponse, AppError> {
+    negotiate_arrow_format(&mut params.arrow_format, &headers);
+    catalog::handle(&app_state, database, None, params).await
+}
+
+#[axum::debug_handler]
+async fn catalog_objects_handler(
+    State(app_state): State<Arc<AppState>>,
+    Path((database, object)): Path<(String, CatalogObject)>,
+    headers: HeaderMap,
+    Query(mut params): Query<CatalogParams>,
+) -> Result<QueryResponse, AppError> {
+    negotiate_arrow_format(&mut params.arrow_format, &headers);
+    catalog::handle(&app_state, database, Some(object), params).await
+}
+
+#[axum::debug_handler]
+async fn upload_handler(
+    State(app_state): State<Arc<AppState>>,
+    Path((database, table)): Path<(String, String)>,
+    Query(params): Query<UploadParams>,
+    request: Request<Body>,
+) -> Result<Json<UploadInfo>, AppError> {
+    Ok(Json(upload::upload(&app_state, database, table, params, request).await?))
+}
+
 #[axum::debug_handler]
 async fn list_queries_handler(State(app_state): State<Arc<AppState>>) -> Result<QueryResponse, AppError> {
     query::list_running_queries(&app_state).await
@@ -219,11 +245,23 @@ pub async fn app(app_state: Arc<AppState>, timeout: u32, auth_config: Option<Aut
             .route("/query/", get(handle_get).post(handle_post))
             .route("/healthz", get(readiness_probe))
             .route("/version", get(version_handler))
-            .route("/query/{query_id}", delete(cancel_query_handler))
+            .route("/query/{query_id}", get(async_query_status_handler).delete(cancel_query_handler))
+            .route("/query/{query_id}/result", get(async_query_result_handler))
             .route("/queries", get(list_queries_handler))
             .route("/queries/killall", delete(kill_all_connections_handler))
             .route("/queries/{database}/killall", delete(killall_queries_for_database_handler))
             .route("/status", get(status_handler))
+            .route("/prepared", post(prepare_handler))
+            .route("/prepared/{name}/execute", post(execute_prepared_handler))
+            .route("/sessions", post(open_session_handler))
+            .route("/sessions/{session_id}", delete(close_session_handler))
+            .route("/databases/{database}/catalog", get(catalogs_handler))
+            .route("/databases/{database}/catalog/{object}", get(catalog_objects_handler))
+            .route(
+                "/databases/{database}/tables/{table}/upload",
+                post(upload_handler).layer(DefaultBodyLimit::disable()),
+            )
+            .route("/ws", get(ws::ws_handler))
             .with_state(app_state)
             .layer(axum::middleware::from_fn_with_state(
                 auth_config,
@@ -241,13 +279,25 @@ pub async fn app(app_state: Arc<AppState>, timeout: u32, auth_config: Option<Aut
             .route("/", get(readiness_probe))
             .route("/query", get(handle_get).post(handle_post))
             .route("/query/", get(handle_get).post(handle_post))
-            .route("/query/{query_id}", delete(cancel_query_handler))
+            .route("/query/{query_id}", get(async_query_status_handler).delete(cancel_query_handler))
+            .route("/query/{query_id}/result", get(async_query_result_handler))
             .route("/queries", get(list_queries_handler))
             .route("/queries/killall", delete(kill_all_connections_handler))
             .route("/queries/{database}/killall", delete(killall_queries_for_database_handler))
             .route("/healthz", get(readiness_probe))
             .route("/version", get(version_handler))
             .route("/status", get(status_handler))
+            .route("/prepared", post(prepare_handler))
+            .route("/prepared/{name}/execute", post(execute_prepared_handler))
+            .route("/sessions", post(open_session_handler))
+            .route("/sessions/{session_id}", delete(close_session_handler))
+            .route("/databases/{database}/catalog", get(catalogs_handler))
+            .route("/databases/{database}/catalog/{object}", get(catalog_objects_handler))
+            .route(
+                "/databases/{database}/tables/{table}/upload",
+                post(upload_handler).layer(DefaultBodyLimit::disable()),
+            )
+            .route("/ws", get(ws::ws_handler))
             .with_state(app_state)
             .layer(SentryHttpLayer::new().enable_transaction())
             .layer(NewSentryLayer::<Request<Body>>::new_from_top())
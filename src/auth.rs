@@ -4,7 +4,9 @@ use axum::{
     middleware::Next,
     response::Response,
 };
+use base64::{Engine, engine::general_purpose::STANDARD};
 use subtle::ConstantTimeEq;
+use tonic::{Status, service::Interceptor};
 
 #[derive(Debug, Clone)]
 pub struct AuthConfig {
@@ -62,7 +64,7 @@ pub async fn selective_auth_middleware(
     Ok(next.run(request).await)
 }
 
-fn validate_auth_token(token: &str, config: &AuthConfig) -> bool {
+pub(crate) fn validate_auth_token(token: &str, config: &AuthConfig) -> bool {
     if let Some(expected_token) = &config.auth_token {
         token.as_bytes().ct_eq(expected_token.as_bytes()).into()
     } else {
@@ -71,6 +73,58 @@ fn validate_auth_token(token: &str, config: &AuthConfig) -> bool {
     }
 }
 
+/// Marks a Flight call whose bearer token was accepted by [`flight_auth_interceptor`].
+#[derive(Debug, Clone, Copy)]
+pub struct FlightAuthenticated;
+
+/// Checks the `authorization` metadata of every Flight call against the HTTP auth config.
+///
+/// A wrong bearer token is rejected immediately. Calls without one pass through unmarked, so
+/// that `handshake` and the `healthcheck` action stay public; every other Flight method
+/// refuses them through [`authorize_flight`].
+#[allow(clippy::result_large_err)]
+pub fn flight_auth_interceptor(auth_config: Option<AuthConfig>) -> impl Interceptor + Clone {
+    move |mut request: tonic::Request<()>| {
+        let Some(config) = auth_config.as_ref().filter(|config| config.require_auth) else {
+            request.extensions_mut().insert(FlightAuthenticated);
+            return Ok(request);
+        };
+
+        let auth_header = request
+            .metadata()
+            .get(AUTHORIZATION.as_str())
+            .and_then(|header| header.to_str().ok());
+
+        if let Some(token) = auth_header.and_then(|header| header.strip_prefix("Bearer ")) {
+            if !validate_auth_token(token, config) {
+                tracing::warn!("Invalid authentication token for Flight call");
+                return Err(Status::unauthenticated("Invalid authentication token"));
+            }
+            request.extensions_mut().insert(FlightAuthenticated);
+        }
+
+        Ok(request)
+    }
+}
+
+#[allow(clippy::result_large_err)]
+pub fn authorize_flight<T>(request: &tonic::Request<T>) -> Result<(), Status> {
+    if request.extensions().get::<FlightAuthenticated>().is_some() {
+        Ok(())
+    } else {
+        Err(Status::unauthenticated("Missing authentication token"))
+    }
+}
+
+/// Extracts the password of an `authorization: Basic` header, which Flight clients send
+/// during `handshake` (e.g. pyarrow's `authenticate_basic_token`). The user name is ignored.
+pub fn basic_auth_password(header: &str) -> Option<String> {
+    let credentials = STANDARD.decode(header.strip_prefix("Basic ")?).ok()?;
+    let credentials = String::from_utf8(credentials).ok()?;
+    let (_, password) = credentials.split_once(':')?;
+    Some(password.to_string())
+}
+
 pub fn create_auth_config(
     require_auth: bool,
     auth_token: Option<String>,
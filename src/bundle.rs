@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::constants::{BUNDLE_DIRECTORY, BUNDLE_MANIFEST};
+use crate::interfaces::{AppError, Command, DbState, QueryParams, QueryResponse};
+use crate::query::{self, RunningQueryGuard, result_cache_key};
+use crate::sql::is_single_query;
+use crate::state::AppState;
+
+/// The `bundle.json` of a bundle directory.
+#[derive(Serialize, Deserialize, Default)]
+struct BundleManifest {
+    /// Statements that are not queries, replayed in order when the bundle is loaded.
+    exec: Vec<String>,
+    /// Cache keys of the Arrow results, each stored in a file of the same name.
+    queries: Vec<String>,
+}
+
+fn bundle_directory(state: &AppState, params: &QueryParams) -> Result<PathBuf, AppError> {
+    let name = params
+        .name
+        .as_deref()
+        .ok_or_else(|| AppError::BadRequest(anyhow::anyhow!("Bundle name is required").into()))?;
+
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(AppError::BadRequest(
+            anyhow::anyhow!("Bundle name may only contain letters, digits, '-' and '_'").into(),
+        ));
+    }
+
+    Ok(Path::new(&state.root).join(BUNDLE_DIRECTORY).join(name))
+}
+
+/// Resolves a result file named in a manifest, refusing anything outside the bundle directory.
+fn bundle_file(directory: &Path, key: &str) -> Result<PathBuf, AppError> {
+    if Path::new(key).file_name().and_then(|name| name.to_str()) != Some(key) {
+        return Err(AppError::BadRequest(anyhow::anyhow!("Invalid bundle entry: {}", key).into()));
+    }
+
+    Ok(directory.join(key))
+}
+
+/// A query against the bundle's database that inherits the request's connection settings.
+fn bundle_query(params: &QueryParams, command: Command, sql: &str) -> QueryParams {
+    QueryParams {
+        database: params.database.clone(),
+        query_type: Some(command),
+        sql: Some(sql.to_string()),
+        prepare_sql: params.prepare_sql.clone(),
+        default_schema: params.default_schema.clone(),
+        extensions: params.extensions.clone(),
+        secrets: params.secrets.clone(),
+        ducklakes: params.ducklakes.clone(),
+        ..Default::default()
+    }
+}
+
+/// Whether a bundle statement is a query whose result is saved. A statement sqlparser cannot
+/// read, like some DuckDB-only syntax, is a query if DuckDB can select from it.
+async fn is_query(db_state: &DbState, params: &QueryParams, sql: &str) -> bool {
+    if let Some(is_query) = is_single_query(sql) {
+        return is_query;
+    }
+
+    let probe = format!("SELECT * FROM ({}) LIMIT 0", sql.trim().trim_end_matches(';'));
+    db_state
+        .db
+        .get_record_batches(
+            &probe,
+            &None,
+            &None,
+            &params.prepare_sql,
+            &params.default_schema,
+            &None,
+            0,
+            &params.extensions,
+            &params.secrets,
+            &params.ducklakes,
+            &Default::default(),
+        )
+        .await
+        .is_ok()
+}
+
+/// Runs the `queries` of a `create-bundle` request and saves them under the database root.
+/// Statements that are not queries (e.g. `CREATE TABLE`) are executed and recorded; every
+/// query has its Arrow result cached and written to a file named after its cache key.
+pub async fn create(state: &Arc<AppState>, params: &QueryParams) -> Result<QueryResponse, AppError> {
+    let directory = bundle_directory(state, params)?;
+    let queries = params
+        .queries
+        .as_ref()
+        .ok_or_else(|| AppError::BadRequest(anyhow::anyhow!("Bundle queries are required").into()))?;
+
+    tokio::fs::create_dir_all(&directory).await?;
+
+    let db_state = state
+        .get_or_create_db_state(&params.database, &params.extensions, &params.secrets, &params.ducklakes)
+        .await?;
+
+    let mut manifest = BundleManifest::default();
+
+    for sql in queries {
+        if !is_query(&db_state, params, sql).await {
+            Box::pin(query::handle(state, &bundle_query(params, Command::Exec, sql))).await?;
+            manifest.exec.push(sql.clone());
+            continue;
+        }
+
+        let mut query = bundle_query(params, Command::Arrow, sql);
+        query.persist = Some(true);
+
+        let key = result_cache_key(sql, &query, &Command::Arrow);
+        let body = Box::pin(query::handle(state, &query)).await?.into_body().await?;
+        tokio::fs::write(bundle_file(&directory, &key)?, body).await?;
+        manifest.queries.push(key);
+    }
+
+    tokio::fs::write(directory.join(BUNDLE_MANIFEST), serde_json::to_vec_pretty(&manifest)?).await?;
+
+    tracing::info!(
+        "Created bundle {} with {} statements and {} queries",
+        directory.display(),
+        manifest.exec.len(),
+        manifest.queries.len()
+    );
+
+    Ok(QueryResponse::Empty)
+}
+
+/// Restores a bundle written by [`create`]: its statements are replayed against the database
+/// in one transaction, so a replay that fails (e.g. on a table the bundle already created)
+/// leaves nothing behind, and the saved Arrow results go back into the database's result cache.
+pub async fn load(state: &Arc<AppState>, params: &QueryParams) -> Result<QueryResponse, AppError> {
+    let directory = bundle_directory(state, params)?;
+
+    let manifest = tokio::fs::read(directory.join(BUNDLE_MANIFEST)).await.map_err(|e| {
+        AppError::BadRequest(anyhow::anyhow!("Cannot read bundle {}: {}", directory.display(), e).into())
+    })?;
+    let manifest: BundleManifest = serde_json::from_slice(&manifest)?;
+
+    let db_state = state
+        .get_or_create_db_state(
+            &params.database,
+            &params.extensions,
+            &params.secrets,
+            &params.ducklakes
+        )
+        .await?;
+
+    let (query_id, cancel_token) = query::register_query(state, params, &manifest.exec.join(";\n")).await?;
+    let _guard = RunningQueryGuard {
+        state: Arc::clone(state),
+        query_id,
+        cancel_token: cancel_token.clone(),
+    };
+
+    db_state
+        .db
+        .execute_transaction(&manifest.exec, &params.default_schema, &params.extensions, &cancel_token)
+        .await?;
+
+    for key in &manifest.queries {
+        let body = tokio::fs::read(bundle_file(&directory, key)?).await?;
+        db_state.cache.lock().await.put(key.clone(), body);
+    }
+
+    tracing::info!(
+        "Loaded bundle {} with {} statements and {} queries",
+        directory.display(),
+        manifest.exec.len(),
+        manifest.queries.len()
+    );
+
+    Ok(QueryResponse::Empty)
+}
@@ -1,11 +1,16 @@
-use anyhow::Result;
 use serde_json::to_value;
+use std::collections::BTreeMap;
 use tokio::sync::Mutex;
 
 use crate::interfaces::{Command, SqlValue};
 
 #[must_use]
-pub fn get_key(sql: &str, args: &Option<Vec<SqlValue>>, command: &Command) -> String {
+pub fn get_key(
+    sql: &str,
+    args: &Option<Vec<SqlValue>>,
+    named_args: &Option<BTreeMap<String, SqlValue>>,
+    command: &Command,
+) -> String {
     use sha2::{Digest, Sha256};
     let mut hasher = Sha256::new();
     hasher.update(sql);
@@ -17,6 +22,13 @@ pub fn get_key(sql: &str, args: &Option<Vec<SqlValue>>, command: &Command) -> St
         }
     }
 
+    if let Some(named_args) = named_args {
+        for (name, arg) in named_args {
+            let arg_str = format!("${}={:?}", name, arg);
+            hasher.update(arg_str);
+        }
+    }
+
     format!(
         "{:x}.{}",
         hasher.finalize(),
@@ -24,48 +36,24 @@ pub fn get_key(sql: &str, args: &Option<Vec<SqlValue>>, command: &Command) -> St
     )
 }
 
-pub async fn retrieve<F, Fut>(
-    cache: &Mutex<lru::LruCache<String, Vec<u8>>>,
-    sql: &str,
-    args: &Option<Vec<SqlValue>>,
-    command: &Command,
-    persist: bool,
-    invalidate: bool,
-    f: F,
-) -> Result<Vec<u8>>
-where
-    F: FnOnce() -> Fut,
-    Fut: std::future::Future<Output = Result<Vec<u8>>>,
-{
-    let key = get_key(sql, args, command);
-
+/// Returns the cached result stored under `key`, unless `invalidate` asks to drop it instead.
+pub async fn lookup(cache: &Mutex<lru::LruCache<String, Vec<u8>>>, key: &str, invalidate: bool) -> Option<Vec<u8>> {
     if invalidate {
-        flush(cache, sql, args, command).await;
-    }
-    else if let Some(cached) = cache.lock().await.get(&key) {
-        tracing::debug!("Cache hit {}!", key);
-        return Ok(cached.clone());
+        flush(cache, key).await;
+        return None;
     }
 
-    let result = f().await?;
-
-    if persist {
-        cache.lock().await.put(key, result.clone());
+    let cached = cache.lock().await.get(key).cloned();
+    if cached.is_some() {
+        tracing::debug!("Cache hit {}!", key);
     }
 
-    Ok(result)
+    cached
 }
 
-pub async fn flush(
-    cache: &Mutex<lru::LruCache<String, Vec<u8>>>, 
-    sql: &str, 
-    args: &Option<Vec<SqlValue>>, 
-    command: &Command
-) {
-    let key = get_key(sql, args, command);
-
+pub async fn flush(cache: &Mutex<lru::LruCache<String, Vec<u8>>>, key: &str) {
     let mut cache_lock = cache.lock().await;
-    if cache_lock.pop(&key).is_some() {
+    if cache_lock.pop(key).is_some() {
         tracing::info!("Cache entry cleared for key: {}", key);
     }
     else {
@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use crate::constants::METADATA_ROW_LIMIT;
+use crate::interfaces::{AppError, CatalogObject, CatalogParams, Command, QueryParams, QueryResponse, SqlValue};
+use crate::query;
+use crate::state::AppState;
+
+/// Lists the catalogs attached to the database, ducklakes included, or, given an `object`,
+/// their schemas, tables and views, columns or constraints. The listing runs as a query on
+/// the database's pool, so `persist` keeps it in the result cache like any other result.
+/// Unlike queries, listings are complete unless the request sets a `limit`.
+pub async fn handle(
+    state: &Arc<AppState>,
+    database: String,
+    object: Option<CatalogObject>,
+    params: CatalogParams,
+) -> Result<QueryResponse, AppError> {
+    let command = match params.query_type {
+        None | Some(Command::Json) => Command::Json,
+        Some(Command::Arrow) => Command::Arrow,
+        Some(command) => {
+            return Err(AppError::BadRequest(
+                anyhow::anyhow!("Catalog listings come as json or arrow, not {:?}", command).into(),
+            ));
+        }
+    };
+
+    // Each listing names the columns that `catalog`, `schema` and `table` filter on
+    let (listing, filters, order) = match object {
+        None => (
+            "SELECT database_name AS catalog, type, path, readonly, comment FROM duckdb_databases() WHERE NOT internal",
+            [Some("catalog"), None, None],
+            "catalog",
+        ),
+        Some(CatalogObject::Schemas) => (
+            // `main` counts as internal, so the system schemas are left out by name
+            "SELECT database_name AS catalog, schema_name AS schema, comment FROM duckdb_schemas() \
+            WHERE database_name NOT IN ('system', 'temp') AND schema_name NOT IN ('information_schema', 'pg_catalog')",
+            [Some("catalog"), Some("schema"), None],
+            "catalog, schema",
+        ),
+        Some(CatalogObject::Tables) => (
+            "SELECT database_name AS catalog, schema_name AS schema, table_name AS name, 'table' AS type, \
+                estimated_size AS estimated_rows, column_count, comment \
+            FROM duckdb_tables() WHERE NOT internal \
+            UNION ALL \
+            SELECT database_name, schema_name, view_name, 'view', NULL, column_count, comment \
+            FROM duckdb_views() WHERE NOT internal",
+            [Some("catalog"), Some("schema"), Some("name")],
+            "catalog, schema, name",
+        ),
+        Some(CatalogObject::Columns) => (
+            "SELECT database_name AS catalog, schema_name AS schema, table_name AS \"table\", column_name AS name, \
+                column_index AS position, data_type, is_nullable AS nullable, column_default AS \"default\", comment \
+            FROM duckdb_columns() WHERE NOT internal",
+            [Some("catalog"), Some("schema"), Some("\"table\"")],
+            "catalog, schema, \"table\", position",
+        ),
+        Some(CatalogObject::Constraints) => (
+            "SELECT database_name AS catalog, schema_name AS schema, table_name AS \"table\", constraint_name AS name, \
+                constraint_type AS type, constraint_column_names AS columns, constraint_text AS definition, \
+                referenced_table, referenced_column_names AS referenced_columns \
+            FROM duckdb_constraints()",
+            [Some("catalog"), Some("schema"), Some("\"table\"")],
+            "catalog, schema, \"table\", name",
+        ),
+    };
+
+    let mut conditions = Vec::new();
+    let mut args = Vec::new();
+    for (column, value) in filters.iter().zip([params.catalog, params.schema, params.table]) {
+        if let (Some(column), Some(value)) = (column, value) {
+            conditions.push(format!("{} = ?", column));
+            args.push(SqlValue::Text(value));
+        }
+    }
+
+    let mut sql = format!("SELECT * FROM ({})", listing);
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    sql.push_str(&format!(" ORDER BY {}", order));
+
+    let params = QueryParams {
+        database,
+        query_type: Some(command),
+        sql: Some(sql),
+        args: Some(args),
+        arrow_format: params.arrow_format,
+        compression: params.compression,
+        persist: params.persist,
+        invalidate: params.invalidate,
+        limit: Some(params.limit.unwrap_or(METADATA_ROW_LIMIT)),
+        ..Default::default()
+    };
+
+    query::with_db_retry(state, &params, |state, params| Box::pin(query::handle(state, params))).await
+}
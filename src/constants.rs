@@ -1,5 +1,6 @@
 use git_version::git_version;
 use once_cell::sync::Lazy;
+use std::time::Duration;
 
 #[allow(unused)]
 pub const DEFAULT_CACHE_SIZE: usize = 1000;
@@ -8,6 +9,46 @@ pub const GIT_VERSION: &str = git_version!(fallback = env!("GIT_HASH"));
 #[allow(unused)]
 pub const DEFAULT_ROW_LIMIT: usize = 2000;
 
+/// Metadata listings are never truncated by the row limit.
+#[allow(unused)]
+pub const METADATA_ROW_LIMIT: usize = i64::MAX as usize;
+
+#[allow(unused)]
+pub const RECORD_BATCH_CHANNEL_CAPACITY: usize = 4;
+
+#[allow(unused)]
+pub const PREPARED_STATEMENT_CACHE_CAPACITY: usize = 64;
+
+#[allow(unused)]
+pub const WS_CHANNEL_CAPACITY: usize = 16;
+
+#[allow(unused)]
+pub const MAX_CACHED_RESULT_BYTES: usize = 16 * 1024 * 1024;
+
+#[allow(unused)]
+pub const PARAMETER_ROW_COLUMN: &str = "parameter_row";
+
+#[allow(unused)]
+pub const FLIGHT_TICKET_TTL: Duration = Duration::from_secs(600);
+
+#[allow(unused)]
+pub const STATE_SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+#[allow(unused)]
+pub const CURSOR_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[allow(unused)]
+pub const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[allow(unused)]
+pub const BUNDLE_DIRECTORY: &str = ".bundles";
+
+#[allow(unused)]
+pub const BUNDLE_MANIFEST: &str = "bundle.json";
+
+#[allow(unused)]
+pub const UPLOAD_DIRECTORY: &str = ".uploads";
+
 #[allow(unused)]
 pub const MEMORY_DB_PATH: &str = ":memory:";
 
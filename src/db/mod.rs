@@ -7,4 +7,5 @@ mod traits;
 pub mod monitoring;
 
 pub use pool::ConnectionPool;
-pub use traits::Database;
+pub use queries::{SchemaGuard, TimeZoneGuard, inline_args, statement_args};
+pub use traits::{Database, RecordBatchStream};
@@ -1,5 +1,6 @@
 use anyhow::Result;
 use duckdb::{AccessMode, Config, Connection, DuckdbConnectionManager};
+use std::collections::HashMap;
 use std::os::unix::fs::MetadataExt;
 use std::time::Duration;
 use tracing::log::info;
@@ -9,7 +10,7 @@ use crate::interfaces::{AppError, DbType, DucklakeConfig, Extension, SecretConfi
 
 use super::config::{load_extensions, setup_ducklakes, setup_secrets};
 use super::instance_cache::CachedConnectionManager;
-use super::traits::PoolStatus;
+use super::traits::{PoolStatus, PreparedStatement};
 
 pub(crate) enum PoolType {
     File(r2d2::Pool<DuckdbConnectionManager>),
@@ -69,6 +70,7 @@ pub struct ConnectionPool {
     pub(crate) extensions: parking_lot::RwLock<Option<Vec<Extension>>>,
     pub(crate) secrets: parking_lot::RwLock<Option<Vec<SecretConfig>>>,
     pub(crate) ducklakes: parking_lot::RwLock<Option<Vec<DucklakeConfig>>>,
+    pub(crate) prepared: parking_lot::RwLock<HashMap<String, PreparedStatement>>,
 }
 
 impl ConnectionPool {
@@ -117,6 +119,7 @@ impl ConnectionPool {
             extensions: parking_lot::RwLock::new(extensions.clone()),
             secrets: parking_lot::RwLock::new(secrets.clone()),
             ducklakes: parking_lot::RwLock::new(ducklakes.clone()),
+            prepared: parking_lot::RwLock::new(HashMap::new()),
         })
     }
 
@@ -174,6 +177,11 @@ impl ConnectionPool {
     pub fn reset_pool(&self, new_inode: Option<u64>) -> Result<()> {
         let (new_pool, detected_inode) = self.reset_pool_internal()?;
         *self.pool.write() = new_pool;
+        // New connections prepare registered statements again when they first run them, but a
+        // write may have changed what the statements return
+        for statement in self.prepared.write().values_mut() {
+            statement.schema_outdated = true;
+        }
 
         if new_inode.is_some() {
             *self.inode.write() = new_inode;
@@ -1,13 +1,31 @@
 use anyhow::Result;
-use arrow::record_batch::RecordBatch;
+use arrow::{
+    array::{ArrayRef, UInt64Array},
+    compute::cast,
+    datatypes::{DataType, Field, Schema, SchemaRef},
+    record_batch::RecordBatch,
+};
 use async_trait::async_trait;
-use duckdb::{params_from_iter, types::ToSql};
+use duckdb::{
+    Statement,
+    params_from_iter,
+    types::ToSql,
+    vtab::arrow::{ArrowVTab, arrow_recordbatch_to_query_params},
+};
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use std::time::Instant;
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::wrappers::ReceiverStream;
 use tokio_util::sync::CancellationToken;
 
-use crate::interfaces::{AppError, DucklakeConfig, Extension, SecretConfig, SqlValue};
-use crate::sql::{enforce_query_limit, is_writable_sql};
+use crate::constants::{PARAMETER_ROW_COLUMN, PREPARED_STATEMENT_CACHE_CAPACITY, RECORD_BATCH_CHANNEL_CAPACITY};
+
+use crate::interfaces::{AppError, DucklakeConfig, Extension, IngestMode, SecretConfig, SqlValue};
+use crate::sql::{
+    enforce_query_limit, is_single_statement, is_writable_sql, quote_identifier, quote_literal, replace_placeholders,
+    schema_probe_sql,
+};
 
 use super::config::{
     load_extensions, merge_ducklakes, merge_extensions, merge_secrets,
@@ -15,56 +33,170 @@ use super::config::{
 };
 use super::monitoring::{catch_query_panic, log_query_completed};
 use super::pool::ConnectionPool;
-use super::traits::{Database, PoolStatus};
+use super::traits::{Database, PoolStatus, PreparedStatement, RecordBatchStream};
 
 #[async_trait]
 impl Database for Arc<ConnectionPool> {
-    async fn execute(&self, sql: &str, default_schema: &Option<String>, extensions: &Option<Vec<Extension>>) -> Result<()> {
-        let conn = self.get().map_err(|e| anyhow::anyhow!("{}", e))?;
+    async fn execute(
+        &self,
+        sql: &str,
+        parameter_rows: &[Vec<SqlValue>],
+        default_schema: &Option<String>,
+        extensions: &Option<Vec<Extension>>,
+        cancel_token: &CancellationToken,
+    ) -> Result<usize> {
+        let pool = Arc::clone(self);
+        let sql_owned = sql.to_string();
+        let parameter_rows = parameter_rows.to_vec();
+        let default_schema_owned = default_schema.clone();
+        let extensions_owned = extensions.clone();
+        let cancel_token = cancel_token.clone();
+        let runtime = tokio::runtime::Handle::current();
 
-        if let Some(exts) = extensions {
-            load_extensions(&conn, exts)?;
-        }
+        let rows = tokio::task::spawn_blocking(move || {
+            catch_query_panic(&sql_owned, || {
+                let conn = pool.get().map_err(|e| anyhow::anyhow!("{}", e))?;
 
-        if let Some(default_schema) = default_schema {
-            conn.execute_batch(&format!("USE {}", default_schema))?;
-        }
+                if let Some(exts) = extensions_owned {
+                    load_extensions(&conn, &exts)?;
+                }
+
+                if let Some(default_schema) = default_schema_owned {
+                    conn.execute_batch(&format!("USE {}", default_schema))?;
+                }
+
+                // Interrupt DuckDB when the statement is cancelled
+                let interrupt = conn.interrupt_handle();
+                let watcher = runtime.spawn(async move {
+                    cancel_token.cancelled().await;
+                    interrupt.interrupt();
+                });
+
+                let start = Instant::now();
+                let result = if parameter_rows.len() > 1 {
+                    conn.execute_batch("BEGIN TRANSACTION")?;
+                    let result = execute_rows(&conn, &sql_owned, &parameter_rows);
+                    match result {
+                        Ok(_) => conn.execute_batch("COMMIT")?,
+                        Err(_) => {
+                            let _ = conn.execute_batch("ROLLBACK");
+                        }
+                    }
+                    result
+                }
+                else {
+                    execute_rows(&conn, &sql_owned, &parameter_rows)
+                };
+                watcher.abort();
+                let rows = result?;
+
+                log_query_completed(start, &conn, &sql_owned);
 
-        conn.execute_batch(sql)?;
+                Ok(rows)
+            })
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("Task error: {}", e))??;
 
         if is_writable_sql(sql) {
             self.reset_pool(None)?;
         }
 
+        Ok(rows)
+    }
+
+    async fn execute_transaction(
+        &self,
+        statements: &[String],
+        default_schema: &Option<String>,
+        extensions: &Option<Vec<Extension>>,
+        cancel_token: &CancellationToken,
+    ) -> Result<()> {
+        let pool = Arc::clone(self);
+        let statements_owned = statements.to_vec();
+        let default_schema_owned = default_schema.clone();
+        let extensions_owned = extensions.clone();
+        let cancel_token = cancel_token.clone();
+        let runtime = tokio::runtime::Handle::current();
+        let transaction_sql = statements.join(";\n");
+
+        tokio::task::spawn_blocking(move || {
+            catch_query_panic(&transaction_sql, || {
+                let conn = pool.get().map_err(|e| anyhow::anyhow!("{}", e))?;
+
+                if let Some(exts) = extensions_owned {
+                    load_extensions(&conn, &exts)?;
+                }
+
+                if let Some(default_schema) = default_schema_owned {
+                    conn.execute_batch(&format!("USE {}", default_schema))?;
+                }
+
+                // Interrupt DuckDB when the transaction is cancelled
+                let interrupt = conn.interrupt_handle();
+                let watcher = runtime.spawn(async move {
+                    cancel_token.cancelled().await;
+                    interrupt.interrupt();
+                });
+
+                let start = Instant::now();
+                conn.execute_batch("BEGIN TRANSACTION")?;
+
+                let result = statements_owned.iter().try_for_each(|sql| conn.execute_batch(sql));
+
+                match result {
+                    Ok(_) => conn.execute_batch("COMMIT")?,
+                    Err(_) => {
+                        let _ = conn.execute_batch("ROLLBACK");
+                    }
+                }
+                watcher.abort();
+                result?;
+
+                log_query_completed(start, &conn, &transaction_sql);
+
+                Ok(())
+            })
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("Task error: {}", e))??;
+
+        if statements.iter().any(|sql| is_writable_sql(sql)) {
+            self.reset_pool(None)?;
+        }
+
         Ok(())
     }
 
-    async fn get_json(
+    async fn get_record_batches(
         &self,
         sql: &String,
         args: &Option<Vec<SqlValue>>,
+        named_args: &Option<BTreeMap<String, SqlValue>>,
         prepare_sql: &Option<String>,
         default_schema: &Option<String>,
+        timezone: &Option<String>,
         limit: usize,
         extensions: &Option<Vec<Extension>>,
         secrets: &Option<Vec<SecretConfig>>,
         ducklakes: &Option<Vec<DucklakeConfig>>,
         cancel_token: &CancellationToken,
-    ) -> Result<Vec<u8>> {
-        let sql_owned = sql.clone();
-        let prepare_sql_owned = prepare_sql.clone();
+    ) -> Result<Vec<RecordBatch>> {
+        let (sql_owned, args, named_args) =
+            inline_args(sql, args.as_deref().unwrap_or_default(), &named_args.clone().unwrap_or_default())?;
         let effective_sql = enforce_query_limit(&sql_owned, limit)?;
-        let args = args.clone().unwrap_or_default();
         let pool = Arc::clone(self);
+        let prepare_sql_owned = prepare_sql.clone();
         let extensions_owned = extensions.clone();
         let secrets_owned = secrets.clone();
         let ducklakes_owned = ducklakes.clone();
         let default_schema_owned = default_schema.clone();
+        let timezone_owned = timezone.clone();
 
         let result = tokio::select! {
             result = tokio::task::spawn_blocking({
                 let cancel_token = cancel_token.clone();
-                move || -> Result<Vec<u8>> {
+                move || -> Result<Vec<RecordBatch>> {
                     catch_query_panic(&effective_sql, || {
                         let conn = pool.get().map_err(|e| anyhow::anyhow!("{}", e))?;
 
@@ -84,24 +216,25 @@ impl Database for Arc<ConnectionPool> {
                             ducklakes_owned.as_deref(),
                         )?;
 
+                        let _timezone = TimeZoneGuard::set(&conn, timezone_owned.as_deref())?;
+
                         let start = Instant::now();
 
                         let mut stmt = conn.prepare(&effective_sql)?;
-                        let tosql_args: Vec<Box<dyn ToSql>> = args.iter().map(|arg| arg.as_tosql()).collect();
+                        let tosql_args = statement_args(&stmt, &args, &named_args)?;
                         let arrow = stmt.query_arrow(params_from_iter(tosql_args.iter()))?;
 
-                        let buf = Vec::new();
-                        let mut writer = arrow_json::ArrayWriter::new(buf);
+                        let mut batches = Vec::new();
                         for batch in arrow {
                             if cancel_token.is_cancelled() {
                                 return Err(anyhow::anyhow!("Query cancelled"));
                             }
-                            writer.write(&batch)?;
+                            batches.push(batch);
                         }
-                        writer.finish()?;
+
                         log_query_completed(start, &conn, &effective_sql);
 
-                        Ok(writer.into_inner())
+                        Ok(batches)
                     })
                 }
             }) => result.map_err(|e| anyhow::anyhow!("Task error: {}", e))?,
@@ -117,160 +250,624 @@ impl Database for Arc<ConnectionPool> {
         result
     }
 
-    async fn get_arrow(
+    async fn stream_record_batches(
         &self,
         sql: &String,
         args: &Option<Vec<SqlValue>>,
+        named_args: &Option<BTreeMap<String, SqlValue>>,
         prepare_sql: &Option<String>,
         default_schema: &Option<String>,
+        timezone: &Option<String>,
         limit: usize,
         extensions: &Option<Vec<Extension>>,
         secrets: &Option<Vec<SecretConfig>>,
         ducklakes: &Option<Vec<DucklakeConfig>>,
+        prepared: Option<PreparedStatement>,
         cancel_token: &CancellationToken,
-    ) -> Result<Vec<u8>> {
-        let sql_owned = sql.clone();
-        let prepare_sql_owned = prepare_sql.clone();
+    ) -> Result<RecordBatchStream> {
+        let (sql_owned, args, named_args) =
+            inline_args(sql, args.as_deref().unwrap_or_default(), &named_args.clone().unwrap_or_default())?;
+        // Arguments written into a registered statement make it a statement of its own
+        let prepared = prepared.filter(|_| sql_owned == *sql);
+        // A registered statement's schema holds the settings it was registered with
+        let registered_schema = prepared
+            .as_ref()
+            .filter(|_| prepare_sql.is_none() && timezone.is_none())
+            .map(|statement| statement.schema.clone());
+        let cache_statement = prepared.is_some();
         let effective_sql = enforce_query_limit(&sql_owned, limit)?;
-        let args = args.clone().unwrap_or_default();
         let pool = Arc::clone(self);
+        let prepare_sql_owned = prepare_sql.clone();
         let extensions_owned = extensions.clone();
         let secrets_owned = secrets.clone();
         let ducklakes_owned = ducklakes.clone();
         let default_schema_owned = default_schema.clone();
+        let timezone_owned = timezone.clone();
 
-        let result = tokio::select! {
-            result = tokio::task::spawn_blocking({
-                let cancel_token = cancel_token.clone();
-                move || -> Result<Vec<u8>> {
-                    catch_query_panic(&effective_sql, || {
-                        let conn = pool.get().map_err(|e| anyhow::anyhow!("{}", e))?;
+        let (schema_tx, schema_rx) = oneshot::channel::<Result<SchemaRef>>();
+        let (batch_tx, batch_rx) = mpsc::channel::<Result<RecordBatch>>(RECORD_BATCH_CHANNEL_CAPACITY);
+        let runtime = tokio::runtime::Handle::current();
 
-                        if let Some(default_schema) = default_schema_owned {
-                            conn.execute_batch(&format!("USE {}", default_schema))?;
-                        }
+        tokio::task::spawn_blocking({
+            let cancel_token = cancel_token.clone();
+            move || {
+                let mut schema_tx = Some(schema_tx);
 
-                        if let Some(prepare_sql) = prepare_sql_owned {
-                            conn.execute_batch(&prepare_sql)?;
+                let result = catch_query_panic(&effective_sql, || {
+                    let conn = pool.get().map_err(|e| anyhow::anyhow!("{}", e))?;
+
+                    if let Some(default_schema) = default_schema_owned {
+                        conn.execute_batch(&format!("USE {}", default_schema))?;
+                    }
+
+                    if let Some(prepare_sql) = prepare_sql_owned {
+                        conn.execute_batch(&prepare_sql)?;
+                    }
+
+                    setup_and_merge_configs(
+                        &conn,
+                        &pool,
+                        extensions_owned.as_deref(),
+                        secrets_owned.as_deref(),
+                        ducklakes_owned.as_deref(),
+                    )?;
+
+                    let _timezone = TimeZoneGuard::set(&conn, timezone_owned.as_deref())?;
+
+                    // Interrupt DuckDB when the query is cancelled, otherwise a long running
+                    // statement only notices the cancellation between batches.
+                    let interrupt = conn.interrupt_handle();
+                    let watcher = runtime.spawn({
+                        let cancel_token = cancel_token.clone();
+                        async move {
+                            cancel_token.cancelled().await;
+                            interrupt.interrupt();
                         }
+                    });
 
-                        setup_and_merge_configs(
-                            &conn,
-                            &pool,
-                            extensions_owned.as_deref(),
-                            secrets_owned.as_deref(),
-                            ducklakes_owned.as_deref(),
-                        )?;
+                    let start = Instant::now();
 
-                        let start = Instant::now();
+                    let result = (|| -> Result<()> {
+                        // Registered statements stay prepared on the connection
+                        let mut cached;
+                        let mut uncached;
+                        let stmt: &mut Statement = if cache_statement {
+                            conn.set_prepared_statement_cache_capacity(PREPARED_STATEMENT_CACHE_CAPACITY);
+                            cached = conn.prepare_cached(&effective_sql)?;
+                            &mut cached
+                        }
+                        else {
+                            uncached = conn.prepare(&effective_sql)?;
+                            &mut uncached
+                        };
 
-                        let mut stmt = conn.prepare(&effective_sql)?;
-                        let tosql_args: Vec<Box<dyn ToSql>> = args.iter().map(|arg| arg.as_tosql()).collect();
-                        let arrow = stmt.query_arrow(params_from_iter(tosql_args.iter()))?;
+                        // DuckDB only streams results when the schema is known up front, so
+                        // queries are probed first. Anything else is small enough to materialize.
+                        let (schema, batches): (SchemaRef, Box<dyn Iterator<Item = RecordBatch>>) =
+                            match schema_probe_sql(&effective_sql) {
+                                Some(probe_sql) => {
+                                    let schema = match registered_schema {
+                                        Some(schema) => schema,
+                                        None => {
+                                            let mut probe = conn.prepare(&probe_sql)?;
+                                            let probe_args = statement_args(&probe, &args, &named_args)?;
+                                            probe.query_arrow(params_from_iter(probe_args.iter()))?.get_schema()
+                                        }
+                                    };
+                                    let tosql_args = statement_args(stmt, &args, &named_args)?;
+                                    let stream = stmt.stream_arrow(params_from_iter(tosql_args.iter()), schema.clone())?;
+                                    (schema, Box::new(stream))
+                                }
+                                None => {
+                                    let tosql_args = statement_args(stmt, &args, &named_args)?;
+                                    let arrow = stmt.query_arrow(params_from_iter(tosql_args.iter()))?;
+                                    (arrow.get_schema(), Box::new(arrow))
+                                }
+                            };
 
-                        let schema = arrow.get_schema();
-                        let mut buffer: Vec<u8> = Vec::new();
-                        let mut writer = arrow_ipc::writer::FileWriter::try_new(&mut buffer, schema.as_ref())?;
-                        for batch in arrow {
+                        if let Some(tx) = schema_tx.take() {
+                            let _ = tx.send(Ok(schema));
+                        }
+
+                        for batch in batches {
                             if cancel_token.is_cancelled() {
                                 return Err(anyhow::anyhow!("Query cancelled"));
                             }
-                            writer.write(&batch)?;
+                            // A closed channel means the consumer went away, so stop the query.
+                            if batch_tx.blocking_send(Ok(batch)).is_err() {
+                                cancel_token.cancel();
+                                return Err(anyhow::anyhow!("Query cancelled"));
+                            }
                         }
-                        writer.finish()?;
+
                         log_query_completed(start, &conn, &effective_sql);
 
-                        Ok(buffer)
-                    })
+                        Ok(())
+                    })();
+
+                    watcher.abort();
+                    result
+                });
+
+                if is_writable_sql(&sql_owned)
+                    && let Err(e) = pool.reset_pool(None)
+                {
+                    tracing::warn!("Failed to reset pool after streamed query: {}", e);
                 }
-            }) => result.map_err(|e| anyhow::anyhow!("Task error: {}", e))?,
+
+                if let Err(e) = result {
+                    match schema_tx.take() {
+                        Some(tx) => {
+                            let _ = tx.send(Err(e));
+                        }
+                        None => {
+                            let _ = batch_tx.blocking_send(Err(e));
+                        }
+                    }
+                }
+            }
+        });
+
+        let schema = tokio::select! {
+            schema = schema_rx => schema.map_err(|e| anyhow::anyhow!("Task error: {}", e))??,
             _ = cancel_token.cancelled() => {
                 return Err(anyhow::anyhow!("Query cancelled"));
             }
         };
 
-        if is_writable_sql(&sql_owned) {
-            self.reset_pool(None)?;
-        }
-
-        result
+        Ok(RecordBatchStream {
+            schema,
+            batches: ReceiverStream::new(batch_rx),
+        })
     }
 
-    async fn get_record_batches(
+    async fn stream_parameter_batches(
         &self,
         sql: &String,
-        args: &Option<Vec<SqlValue>>,
         prepare_sql: &Option<String>,
         default_schema: &Option<String>,
+        timezone: &Option<String>,
         limit: usize,
         extensions: &Option<Vec<Extension>>,
         secrets: &Option<Vec<SecretConfig>>,
         ducklakes: &Option<Vec<DucklakeConfig>>,
+        mut parameters: mpsc::Receiver<Result<RecordBatch>>,
         cancel_token: &CancellationToken,
-    ) -> Result<Vec<RecordBatch>> {
-        let sql_owned = sql.clone();
-        let effective_sql = enforce_query_limit(&sql_owned, limit)?;
-        let args = args.clone().unwrap_or_default();
+    ) -> Result<RecordBatchStream> {
+        let effective_sql = enforce_query_limit(sql, limit)?;
+        let probe_sql = schema_probe_sql(&effective_sql)
+            .ok_or_else(|| anyhow::anyhow!("Only queries can be run with parameter batches"))?;
         let pool = Arc::clone(self);
         let prepare_sql_owned = prepare_sql.clone();
         let extensions_owned = extensions.clone();
         let secrets_owned = secrets.clone();
         let ducklakes_owned = ducklakes.clone();
         let default_schema_owned = default_schema.clone();
+        let timezone_owned = timezone.clone();
 
-        let result = tokio::select! {
-            result = tokio::task::spawn_blocking({
-                let cancel_token = cancel_token.clone();
-                move || -> Result<Vec<RecordBatch>> {
-                    catch_query_panic(&effective_sql, || {
-                        let conn = pool.get().map_err(|e| anyhow::anyhow!("{}", e))?;
+        let (schema_tx, schema_rx) = oneshot::channel::<Result<SchemaRef>>();
+        let (batch_tx, batch_rx) = mpsc::channel::<Result<RecordBatch>>(RECORD_BATCH_CHANNEL_CAPACITY);
+        let runtime = tokio::runtime::Handle::current();
 
-                        if let Some(default_schema) = default_schema_owned {
-                            conn.execute_batch(&format!("USE {}", default_schema))?;
-                        }
+        tokio::task::spawn_blocking({
+            let cancel_token = cancel_token.clone();
+            move || {
+                let mut schema_tx = Some(schema_tx);
 
-                        if let Some(prepare_sql) = prepare_sql_owned {
-                            conn.execute_batch(&prepare_sql)?;
-                        }
+                let result = catch_query_panic(&effective_sql, || {
+                    let conn = pool.get().map_err(|e| anyhow::anyhow!("{}", e))?;
 
-                        setup_and_merge_configs(
-                            &conn,
-                            &pool,
-                            extensions_owned.as_deref(),
-                            secrets_owned.as_deref(),
-                            ducklakes_owned.as_deref(),
-                        )?;
+                    if let Some(default_schema) = default_schema_owned {
+                        conn.execute_batch(&format!("USE {}", default_schema))?;
+                    }
 
-                        let start = Instant::now();
+                    if let Some(prepare_sql) = prepare_sql_owned {
+                        conn.execute_batch(&prepare_sql)?;
+                    }
+
+                    setup_and_merge_configs(
+                        &conn,
+                        &pool,
+                        extensions_owned.as_deref(),
+                        secrets_owned.as_deref(),
+                        ducklakes_owned.as_deref(),
+                    )?;
+
+                    let _timezone = TimeZoneGuard::set(&conn, timezone_owned.as_deref())?;
+
+                    let interrupt = conn.interrupt_handle();
+                    let watcher = runtime.spawn({
+                        let cancel_token = cancel_token.clone();
+                        async move {
+                            cancel_token.cancelled().await;
+                            interrupt.interrupt();
+                        }
+                    });
 
+                    let start = Instant::now();
+
+                    let result = (|| -> Result<()> {
                         let mut stmt = conn.prepare(&effective_sql)?;
-                        let tosql_args: Vec<Box<dyn ToSql>> = args.iter().map(|arg| arg.as_tosql()).collect();
-                        let arrow = stmt.query_arrow(params_from_iter(tosql_args.iter()))?;
+                        let parameter_count = stmt.parameter_count();
 
-                        let mut batches = Vec::new();
-                        for batch in arrow {
-                            if cancel_token.is_cancelled() {
-                                return Err(anyhow::anyhow!("Query cancelled"));
+                        let mut row_schemas = ParameterRowSchemas::new(&conn, &probe_sql)?;
+
+                        // The first parameter row decides the result schema, and every result batch comes
+                        // from a single parameter row, which leads the batch
+                        let mut output_schema: Option<SchemaRef> = None;
+                        let mut send_schema = |schema: &SchemaRef| {
+                            let mut fields = vec![Arc::new(Field::new(PARAMETER_ROW_COLUMN, DataType::UInt64, false))];
+                            fields.extend(schema.fields().iter().cloned());
+                            let output_schema = Arc::new(Schema::new(fields));
+                            if let Some(tx) = schema_tx.take() {
+                                let _ = tx.send(Ok(output_schema.clone()));
                             }
-                            batches.push(batch);
+                            output_schema
+                        };
+
+                        let mut row_index: u64 = 0;
+                        while let Some(parameter_batch) = parameters.blocking_recv() {
+                            let parameter_batch = parameter_batch?;
+
+                            if parameter_batch.num_columns() != parameter_count {
+                                return Err(anyhow::anyhow!(
+                                    "Query expects {} parameters but the parameter batch has {} columns",
+                                    parameter_count,
+                                    parameter_batch.num_columns()
+                                ));
+                            }
+
+                            for row in 0..parameter_batch.num_rows() {
+                                let args = parameter_batch
+                                    .columns()
+                                    .iter()
+                                    .map(|column| SqlValue::from_arrow(column.as_ref(), row))
+                                    .collect::<Result<Vec<_>>>()?;
+                                let row_schema = row_schemas.get(&parameter_batch, row, &args)?;
+                                let output_schema = output_schema.get_or_insert_with(|| send_schema(&row_schema)).clone();
+                                let tosql_args = args.iter().map(SqlValue::as_tosql).collect::<Result<Vec<_>>>()?;
+
+                                for batch in stmt.stream_arrow(params_from_iter(tosql_args.iter()), row_schema.clone())? {
+                                    if cancel_token.is_cancelled() {
+                                        return Err(anyhow::anyhow!("Query cancelled"));
+                                    }
+
+                                    let mut columns: Vec<ArrayRef> =
+                                        vec![Arc::new(UInt64Array::from(vec![row_index; batch.num_rows()]))];
+                                    for (column, field) in batch.columns().iter().zip(output_schema.fields().iter().skip(1)) {
+                                        let column = cast(column, field.data_type()).map_err(|e| {
+                                            anyhow::anyhow!(
+                                                "Result of parameter row {} does not fit the types of the first row: {}",
+                                                row_index,
+                                                e
+                                            )
+                                        })?;
+                                        columns.push(column);
+                                    }
+                                    let batch = RecordBatch::try_new(output_schema.clone(), columns)?;
+
+                                    // A closed channel means the consumer went away, so stop the query.
+                                    if batch_tx.blocking_send(Ok(batch)).is_err() {
+                                        cancel_token.cancel();
+                                        return Err(anyhow::anyhow!("Query cancelled"));
+                                    }
+                                }
+
+                                row_index += 1;
+                            }
+                        }
+
+                        // Without parameter rows the schema is that of NULL arguments
+                        if output_schema.is_none() {
+                            let nulls = vec![SqlValue::Null; parameter_count];
+                            send_schema(&row_schemas.probe(&nulls)?);
                         }
 
                         log_query_completed(start, &conn, &effective_sql);
 
-                        Ok(batches)
-                    })
+                        Ok(())
+                    })();
+
+                    watcher.abort();
+                    result
+                });
+
+                if let Err(e) = result {
+                    match schema_tx.take() {
+                        Some(tx) => {
+                            let _ = tx.send(Err(e));
+                        }
+                        None => {
+                            let _ = batch_tx.blocking_send(Err(e));
+                        }
+                    }
                 }
-            }) => result.map_err(|e| anyhow::anyhow!("Task error: {}", e))?,
+            }
+        });
+
+        let schema = tokio::select! {
+            schema = schema_rx => schema.map_err(|e| anyhow::anyhow!("Task error: {}", e))??,
             _ = cancel_token.cancelled() => {
                 return Err(anyhow::anyhow!("Query cancelled"));
             }
         };
 
-        if is_writable_sql(&sql_owned) {
-            self.reset_pool(None)?;
+        Ok(RecordBatchStream {
+            schema,
+            batches: ReceiverStream::new(batch_rx),
+        })
+    }
+
+    async fn get_schema(
+        &self,
+        sql: &String,
+        args: &Option<Vec<SqlValue>>,
+        named_args: &Option<BTreeMap<String, SqlValue>>,
+        prepare_sql: &Option<String>,
+        default_schema: &Option<String>,
+        timezone: &Option<String>,
+        extensions: &Option<Vec<Extension>>,
+        secrets: &Option<Vec<SecretConfig>>,
+        ducklakes: &Option<Vec<DucklakeConfig>>,
+    ) -> Result<SchemaRef> {
+        let (sql, args, named_args) =
+            inline_args(sql, args.as_deref().unwrap_or_default(), &named_args.clone().unwrap_or_default())?;
+        let Some(probe_sql) = schema_probe_sql(&sql) else {
+            return Ok(Arc::new(Schema::empty()));
+        };
+
+        let pool = Arc::clone(self);
+        let prepare_sql_owned = prepare_sql.clone();
+        let extensions_owned = extensions.clone();
+        let secrets_owned = secrets.clone();
+        let ducklakes_owned = ducklakes.clone();
+        let default_schema_owned = default_schema.clone();
+        let timezone_owned = timezone.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<SchemaRef> {
+            catch_query_panic(&probe_sql, || {
+                let conn = pool.get().map_err(|e| anyhow::anyhow!("{}", e))?;
+
+                if let Some(default_schema) = default_schema_owned {
+                    conn.execute_batch(&format!("USE {}", default_schema))?;
+                }
+
+                if let Some(prepare_sql) = prepare_sql_owned {
+                    conn.execute_batch(&prepare_sql)?;
+                }
+
+                setup_and_merge_configs(
+                    &conn,
+                    &pool,
+                    extensions_owned.as_deref(),
+                    secrets_owned.as_deref(),
+                    ducklakes_owned.as_deref(),
+                )?;
+
+                let _timezone = TimeZoneGuard::set(&conn, timezone_owned.as_deref())?;
+
+                let mut stmt = conn.prepare(&probe_sql)?;
+
+                // Unbound parameters are probed as NULLs so clients can learn the schema before binding
+                let tosql_args: Vec<Box<dyn ToSql>> = if args.is_empty() && named_args.is_empty() {
+                    (0..stmt.parameter_count()).map(|_| SqlValue::Null.as_tosql()).collect::<Result<_>>()?
+                }
+                else {
+                    statement_args(&stmt, &args, &named_args)?
+                };
+                let arrow = stmt.query_arrow(params_from_iter(tosql_args.iter()))?;
+
+                Ok(arrow.get_schema())
+            })
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("Task error: {}", e))?
+    }
+
+    async fn load_table(&self, schema: &str, table: &str, mode: IngestMode, source: &str) -> Result<usize> {
+        let pool = Arc::clone(self);
+        let target = format!("{}.{}", quote_identifier(schema), quote_identifier(table));
+        let sql = match mode {
+            IngestMode::Create => format!("CREATE TABLE {} AS SELECT * FROM {}", target, source),
+            IngestMode::Replace => format!("CREATE OR REPLACE TABLE {} AS SELECT * FROM {}", target, source),
+            // Files name their columns, so they are matched to the table's by name
+            IngestMode::Append => format!("INSERT INTO {} BY NAME SELECT * FROM {}", target, source),
+        };
+
+        let rows = tokio::task::spawn_blocking(move || -> Result<usize> {
+            catch_query_panic(&sql, || {
+                let conn = pool.get().map_err(|e| anyhow::anyhow!("{}", e))?;
+                let start = Instant::now();
+
+                let rows: i64 = conn.query_row(&sql, [], |row| row.get(0))?;
+
+                log_query_completed(start, &conn, &sql);
+
+                Ok(rows as usize)
+            })
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("Task error: {}", e))??;
+
+        self.reset_pool(None)?;
+
+        Ok(rows)
+    }
+
+    async fn prepare_statement(
+        &self,
+        name: &str,
+        sql: &str,
+        default_schema: &Option<String>,
+    ) -> Result<PreparedStatement> {
+        let sql_owned = sql.to_string();
+        let schema = self
+            .get_schema(&sql_owned, &None, &None, &None, default_schema, &None, &None, &None, &None)
+            .await?;
+
+        let pool = Arc::clone(self);
+        let default_schema_owned = default_schema.clone();
+
+        let parameter_count = tokio::task::spawn_blocking(move || -> Result<usize> {
+            catch_query_panic(&sql_owned, || {
+                let conn = pool.get().map_err(|e| anyhow::anyhow!("{}", e))?;
+
+                if let Some(default_schema) = default_schema_owned {
+                    conn.execute_batch(&format!("USE {}", default_schema))?;
+                }
+
+                Ok(conn.prepare(&sql_owned)?.parameter_count())
+            })
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("Task error: {}", e))??;
+
+        let statement = PreparedStatement {
+            name: name.to_string(),
+            sql: sql.to_string(),
+            default_schema: default_schema.clone(),
+            parameter_count,
+            schema,
+            schema_outdated: false,
+        };
+        self.prepared.write().insert(name.to_string(), statement.clone());
+
+        Ok(statement)
+    }
+
+    async fn get_prepared_statement(&self, name: &str) -> Result<Option<PreparedStatement>> {
+        let Some(statement) = self.prepared.read().get(name).cloned() else {
+            return Ok(None);
+        };
+
+        if !statement.schema_outdated {
+            return Ok(Some(statement));
         }
 
-        result
+        let schema = self
+            .get_schema(&statement.sql, &None, &None, &None, &statement.default_schema, &None, &None, &None, &None)
+            .await?;
+        let statement = PreparedStatement {
+            schema,
+            schema_outdated: false,
+            ..statement
+        };
+
+        // Unless the name was registered again in the meantime
+        if let Some(registered) = self.prepared.write().get_mut(name)
+            && registered.sql == statement.sql
+            && registered.schema_outdated
+        {
+            *registered = statement.clone();
+        }
+
+        Ok(Some(statement))
+    }
+
+    async fn get_parameter_names(&self, sql: &str, default_schema: &Option<String>) -> Result<Vec<String>> {
+        // Preparing runs every statement but the last, so only single statements are prepared
+        if !is_single_statement(sql) {
+            return Ok(vec![]);
+        }
+
+        let pool = Arc::clone(self);
+        let sql_owned = sql.to_string();
+        let default_schema_owned = default_schema.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<Vec<String>> {
+            catch_query_panic(&sql_owned, || {
+                let conn = pool.get().map_err(|e| anyhow::anyhow!("{}", e))?;
+                let _schema = SchemaGuard::set(&conn, default_schema_owned.as_deref())?;
+
+                let stmt = conn.prepare(&sql_owned)?;
+                let names = (1..=stmt.parameter_count())
+                    .map(|index| stmt.parameter_name(index))
+                    .collect::<duckdb::Result<Vec<_>>>()?;
+
+                Ok(names)
+            })
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("Task error: {}", e))?
+    }
+
+    async fn append_record_batches(
+        &self,
+        schema: &str,
+        table: &str,
+        mode: IngestMode,
+        mut batches: mpsc::Receiver<Result<RecordBatch>>,
+    ) -> Result<usize> {
+        let pool = Arc::clone(self);
+        let schema = schema.to_string();
+        let table = table.to_string();
+        let target = format!("{}.{}", quote_identifier(&schema), quote_identifier(&table));
+
+        let rows = tokio::task::spawn_blocking(move || -> Result<usize> {
+            catch_query_panic(&format!("INGEST INTO {}", target), || {
+                let conn = pool.get().map_err(|e| anyhow::anyhow!("{}", e))?;
+                let start = Instant::now();
+
+                conn.execute_batch("BEGIN TRANSACTION")?;
+
+                let result = (|| -> Result<usize> {
+                    let mut appender = None;
+                    let mut rows = 0;
+
+                    while let Some(batch) = batches.blocking_recv() {
+                        let batch = batch?;
+
+                        if appender.is_none() {
+                            appender = Some(if mode == IngestMode::Append {
+                                // Batches name their columns, so they are matched to the table's by name
+                                let mut table_appender = conn.appender_to_db(&table, &schema)?;
+                                for field in batch.schema().fields() {
+                                    table_appender.add_column(field.name())?;
+                                }
+                                table_appender
+                            }
+                            else {
+                                create_table_from_arrow(&conn, &target, batch.schema(), mode)?;
+                                conn.appender_to_db(&table, &schema)?
+                            });
+                        }
+
+                        if batch.num_rows() > 0 {
+                            rows += batch.num_rows();
+                            appender.as_mut().unwrap().append_record_batch(batch)?;
+                        }
+                    }
+
+                    if let Some(mut appender) = appender {
+                        appender.flush()?;
+                    }
+
+                    Ok(rows)
+                })();
+
+                match result {
+                    Ok(_) => conn.execute_batch("COMMIT")?,
+                    Err(_) => {
+                        let _ = conn.execute_batch("ROLLBACK");
+                    }
+                }
+
+                log_query_completed(start, &conn, &format!("INGEST INTO {}", target));
+
+                result
+            })
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("Task error: {}", e))??;
+
+        self.reset_pool(None)?;
+
+        Ok(rows)
+    }
+
+    fn connect(&self) -> Result<duckdb::Connection> {
+        let conn = self.get().map_err(|e| anyhow::anyhow!("{}", e))?;
+        Ok(conn.try_clone()?)
     }
 
     fn reconnect(&self) -> Result<()> {
@@ -286,6 +883,179 @@ impl Database for Arc<ConnectionPool> {
     }
 }
 
+/// Prepares `sql` once and runs it for every parameter row, or once without parameters when
+/// there are none, and returns the number of rows changed.
+fn execute_rows(conn: &duckdb::Connection, sql: &str, parameter_rows: &[Vec<SqlValue>]) -> Result<usize> {
+    let mut stmt = conn.prepare(sql)?;
+
+    if parameter_rows.is_empty() {
+        return Ok(stmt.execute([])?);
+    }
+
+    let mut rows = 0;
+    for args in parameter_rows {
+        let tosql_args = args.iter().map(SqlValue::as_tosql).collect::<Result<Vec<_>>>()?;
+        rows += stmt.execute(params_from_iter(tosql_args.iter()))?;
+    }
+
+    Ok(rows)
+}
+
+/// Arguments in the order of the statement's parameters. Given named arguments, every
+/// parameter is looked up by the name DuckDB reports for it.
+pub fn statement_args(
+    stmt: &Statement,
+    args: &[SqlValue],
+    named_args: &BTreeMap<String, SqlValue>,
+) -> Result<Vec<Box<dyn ToSql>>> {
+    if named_args.is_empty() {
+        return args.iter().map(SqlValue::as_tosql).collect();
+    }
+
+    (1..=stmt.parameter_count())
+        .map(|index| {
+            let name = stmt.parameter_name(index)?;
+            named_args
+                .get(&name)
+                .ok_or_else(|| anyhow::anyhow!("Missing named argument: ${}", name))?
+                .as_tosql()
+        })
+        .collect()
+}
+
+/// duckdb-rs binds only some types of values, so arguments of the others, e.g. `LIST` or `DATE`,
+/// are written into the statement as literals of their type. Returns the statement with the
+/// arguments that are still bound.
+pub fn inline_args(
+    sql: &str,
+    args: &[SqlValue],
+    named_args: &BTreeMap<String, SqlValue>,
+) -> Result<(String, Vec<SqlValue>, BTreeMap<String, SqlValue>)> {
+    if args.iter().chain(named_args.values()).all(SqlValue::is_bindable) {
+        return Ok((sql.to_string(), args.to_vec(), named_args.clone()));
+    }
+
+    let positional = args
+        .iter()
+        .enumerate()
+        .filter(|(_, arg)| !arg.is_bindable())
+        .map(|(index, arg)| Ok((index, arg.sql_literal()?)))
+        .collect::<Result<BTreeMap<_, _>>>()?;
+    let named = named_args
+        .iter()
+        .filter(|(_, arg)| !arg.is_bindable())
+        .map(|(name, arg)| Ok((name.clone(), arg.sql_literal()?)))
+        .collect::<Result<BTreeMap<_, _>>>()?;
+
+    Ok((
+        replace_placeholders(sql, &positional, &named)?,
+        args.iter().filter(|arg| arg.is_bindable()).cloned().collect(),
+        named_args.iter().filter(|(_, arg)| arg.is_bindable()).map(|(name, arg)| (name.clone(), arg.clone())).collect(),
+    ))
+}
+
+/// Result schemas of a parameterized query. Result types follow the types of the bound
+/// arguments, e.g. `SELECT ?` is VARCHAR for a string, so the schema is probed once for every
+/// combination of argument types.
+struct ParameterRowSchemas<'a> {
+    probe: Statement<'a>,
+    schemas: HashMap<Vec<Option<DataType>>, SchemaRef>,
+}
+
+impl<'a> ParameterRowSchemas<'a> {
+    fn new(conn: &'a duckdb::Connection, probe_sql: &str) -> Result<Self> {
+        Ok(ParameterRowSchemas {
+            probe: conn.prepare(probe_sql)?,
+            schemas: HashMap::new(),
+        })
+    }
+
+    /// Schema of the result for `args`, the values of `row` of `parameters`.
+    fn get(&mut self, parameters: &RecordBatch, row: usize, args: &[SqlValue]) -> Result<SchemaRef> {
+        let argument_types = parameters
+            .columns()
+            .iter()
+            .map(|column| (!column.is_null(row)).then(|| column.data_type().clone()))
+            .collect::<Vec<_>>();
+
+        if let Some(schema) = self.schemas.get(&argument_types) {
+            return Ok(schema.clone());
+        }
+
+        let schema = self.probe(args)?;
+        self.schemas.insert(argument_types, schema.clone());
+        Ok(schema)
+    }
+
+    fn probe(&mut self, args: &[SqlValue]) -> Result<SchemaRef> {
+        let tosql_args = args.iter().map(SqlValue::as_tosql).collect::<Result<Vec<_>>>()?;
+        Ok(self.probe.query_arrow(params_from_iter(tosql_args.iter()))?.get_schema())
+    }
+}
+
+/// Sets DuckDB's `TimeZone` on a connection for one query. Dropping the guard puts the previous
+/// setting back, so pooled connections go back to the pool as they were taken out.
+pub struct TimeZoneGuard<'a> {
+    conn: &'a duckdb::Connection,
+    previous: String,
+}
+
+impl<'a> TimeZoneGuard<'a> {
+    pub fn set(conn: &'a duckdb::Connection, timezone: Option<&str>) -> Result<Option<Self>> {
+        let Some(timezone) = timezone else {
+            return Ok(None);
+        };
+
+        let previous: String = conn.query_row("SELECT current_setting('TimeZone')", [], |row| row.get(0))?;
+        conn.execute_batch(&format!("SET TimeZone = {}", quote_literal(timezone)))?;
+
+        Ok(Some(TimeZoneGuard { conn, previous }))
+    }
+}
+
+impl Drop for TimeZoneGuard<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.conn.execute_batch(&format!("SET TimeZone = {}", quote_literal(&self.previous))) {
+            tracing::warn!("Failed to restore time zone {}: {}", self.previous, e);
+        }
+    }
+}
+
+/// Switches a connection to a default schema for one statement. Dropping the guard switches
+/// back, so a connection that outlives the statement keeps its own schema.
+pub struct SchemaGuard<'a> {
+    conn: &'a duckdb::Connection,
+    previous: String,
+}
+
+impl<'a> SchemaGuard<'a> {
+    pub fn set(conn: &'a duckdb::Connection, default_schema: Option<&str>) -> Result<Option<Self>> {
+        let Some(default_schema) = default_schema else {
+            return Ok(None);
+        };
+
+        let (database, schema): (String, String) = conn.query_row(
+            "SELECT current_database(), current_schema()",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        conn.execute_batch(&format!("USE {}", default_schema))?;
+
+        Ok(Some(SchemaGuard {
+            conn,
+            previous: format!("{}.{}", quote_identifier(&database), quote_identifier(&schema)),
+        }))
+    }
+}
+
+impl Drop for SchemaGuard<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.conn.execute_batch(&format!("USE {}", self.previous)) {
+            tracing::warn!("Failed to restore schema {}: {}", self.previous, e);
+        }
+    }
+}
+
 fn setup_and_merge_configs(
     conn: &duckdb::Connection,
     pool: &Arc<ConnectionPool>,
@@ -316,3 +1086,127 @@ fn setup_and_merge_configs(
 
     Ok(())
 }
+
+/// Creates the ingestion target with the columns of an Arrow schema, using DuckDB's
+/// `arrow` table function to translate the types.
+fn create_table_from_arrow(
+    conn: &duckdb::Connection,
+    target: &str,
+    schema: SchemaRef,
+    mode: IngestMode,
+) -> Result<()> {
+    // Registering the function twice on a pooled connection fails; the first registration stays usable.
+    let _ = conn.register_table_function::<ArrowVTab>("arrow");
+
+    let create = match mode {
+        IngestMode::Replace => "CREATE OR REPLACE TABLE",
+        _ => "CREATE TABLE",
+    };
+    let params = arrow_recordbatch_to_query_params(RecordBatch::new_empty(schema));
+    conn.execute(&format!("{} {} AS SELECT * FROM arrow(?, ?)", create, target), params)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Int64Array, StringArray};
+
+    /// The type and text of `arg` as DuckDB sees it after binding or inlining.
+    fn round_trip(arg: serde_json::Value) -> (String, String) {
+        let arg: SqlValue = serde_json::from_value(arg).unwrap();
+        let conn = duckdb::Connection::open_in_memory().unwrap();
+        let (sql, args, named_args) =
+            inline_args("SELECT typeof(?), CAST(? AS VARCHAR)", &[arg.clone(), arg], &BTreeMap::new()).unwrap();
+
+        let mut stmt = conn.prepare(&sql).unwrap();
+        let args = statement_args(&stmt, &args, &named_args).unwrap();
+        stmt.query_row(params_from_iter(args.iter()), |row| Ok((row.get(0)?, row.get(1)?))).unwrap()
+    }
+
+    #[test]
+    fn test_typed_args_round_trip() {
+        let cases = [
+            (serde_json::json!({"type": "boolean", "value": true}), "BOOLEAN", "true"),
+            (serde_json::json!({"type": "tinyint", "value": -8}), "TINYINT", "-8"),
+            (serde_json::json!({"type": "smallint", "value": 300}), "SMALLINT", "300"),
+            (serde_json::json!({"type": "integer", "value": 70000}), "INTEGER", "70000"),
+            (serde_json::json!({"type": "bigint", "value": 5000000000i64}), "BIGINT", "5000000000"),
+            (serde_json::json!({"type": "hugeint", "value": "170141183460469231731687303715884105727"}), "HUGEINT", "170141183460469231731687303715884105727"),
+            (serde_json::json!({"type": "utinyint", "value": 200}), "UTINYINT", "200"),
+            (serde_json::json!({"type": "usmallint", "value": 60000}), "USMALLINT", "60000"),
+            (serde_json::json!({"type": "uinteger", "value": 4000000000u32}), "UINTEGER", "4000000000"),
+            (serde_json::json!({"type": "ubigint", "value": 18000000000000000000u64}), "UBIGINT", "18000000000000000000"),
+            (serde_json::json!({"type": "float", "value": 1.5}), "FLOAT", "1.5"),
+            (serde_json::json!({"type": "double", "value": 2.25}), "DOUBLE", "2.25"),
+            (serde_json::json!({"type": "decimal", "value": "123.45"}), "DECIMAL(5,2)", "123.45"),
+            (serde_json::json!({"type": "varchar", "value": "it's"}), "VARCHAR", "it's"),
+            (serde_json::json!({"type": "blob", "value": "AAEC"}), "BLOB", "\\x00\\x01\\x02"),
+            (serde_json::json!({"type": "date", "value": "2024-01-31"}), "DATE", "2024-01-31"),
+            (serde_json::json!({"type": "time", "value": "12:30:00.5"}), "TIME", "12:30:00.5"),
+            (serde_json::json!({"type": "timestamp", "value": "2024-01-31 12:00:00"}), "TIMESTAMP", "2024-01-31 12:00:00"),
+            (serde_json::json!({"type": "timestamptz", "value": "2024-01-31 12:00:00+02"}), "TIMESTAMP WITH TIME ZONE", "2024-01-31 10:00:00+00"),
+            (serde_json::json!({"type": "interval", "value": "1 day 02:00:00"}), "INTERVAL", "1 day 02:00:00"),
+            (serde_json::json!({"type": "uuid", "value": "a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11"}), "UUID", "a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11"),
+            (serde_json::json!({"type": "list", "value": [{"type": "integer", "value": 1}, {"type": "integer", "value": 2}]}), "INTEGER[]", "[1, 2]"),
+            (serde_json::json!({"type": "struct", "value": {"a": {"type": "date", "value": "2024-01-31"}, "b": "x"}}), "STRUCT(a DATE, b VARCHAR)", "{'a': 2024-01-31, 'b': x}"),
+        ];
+
+        for (arg, data_type, text) in cases {
+            assert_eq!(round_trip(arg.clone()), (data_type.to_string(), text.to_string()), "{}", arg);
+        }
+    }
+
+    #[test]
+    fn test_list_arg_in_list() {
+        let conn = duckdb::Connection::open_in_memory().unwrap();
+        let list: SqlValue = serde_json::from_value(serde_json::json!({"type": "list", "value": [1, 3]})).unwrap();
+        let (sql, args, _) = inline_args(
+            "SELECT count(*) FROM range(5) t(a) WHERE a IN ? AND a < $2",
+            &[list, SqlValue::Int(10)],
+            &BTreeMap::new(),
+        )
+        .unwrap();
+        assert_eq!(args.len(), 1);
+
+        let tosql_args = args.iter().map(SqlValue::as_tosql).collect::<Result<Vec<_>>>().unwrap();
+        let count: i64 = conn.query_row(&sql, params_from_iter(tosql_args.iter()), |row| row.get(0)).unwrap();
+        assert_eq!(count, 2);
+    }
+
+    fn parameters(column: ArrayRef) -> RecordBatch {
+        RecordBatch::try_from_iter(vec![("p", column)]).unwrap()
+    }
+
+    #[test]
+    fn test_parameter_row_schemas_follow_argument_types() {
+        let conn = duckdb::Connection::open_in_memory().unwrap();
+        let probe_sql = schema_probe_sql("SELECT ? AS v").unwrap();
+        let mut schemas = ParameterRowSchemas::new(&conn, &probe_sql).unwrap();
+
+        let strings = parameters(Arc::new(StringArray::from(vec!["a"])));
+        let schema = schemas.get(&strings, 0, &[SqlValue::Text("a".to_string())]).unwrap();
+        assert_eq!(schema.field(0).data_type(), &DataType::Utf8);
+
+        let integers = parameters(Arc::new(Int64Array::from(vec![1])));
+        let schema = schemas.get(&integers, 0, &[SqlValue::Int(1)]).unwrap();
+        assert_eq!(schema.field(0).data_type(), &DataType::Int64);
+    }
+
+    #[test]
+    fn test_parameter_row_schema_streams_string_argument() {
+        let conn = duckdb::Connection::open_in_memory().unwrap();
+        let sql = "SELECT ? AS v";
+        let mut schemas = ParameterRowSchemas::new(&conn, &schema_probe_sql(sql).unwrap()).unwrap();
+        let args = [SqlValue::Text("hello".to_string())];
+        let schema = schemas.get(&parameters(Arc::new(StringArray::from(vec!["hello"]))), 0, &args).unwrap();
+
+        let mut stmt = conn.prepare(sql).unwrap();
+        let tosql_args = args.iter().map(SqlValue::as_tosql).collect::<Result<Vec<_>>>().unwrap();
+        let batches: Vec<RecordBatch> = stmt.stream_arrow(params_from_iter(tosql_args.iter()), schema).unwrap().collect();
+
+        let values = batches[0].column(0).as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(values.value(0), "hello");
+    }
+}
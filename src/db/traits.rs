@@ -1,59 +1,149 @@
 use anyhow::Result;
-use arrow::record_batch::RecordBatch;
+use arrow::{datatypes::SchemaRef, record_batch::RecordBatch};
 use async_trait::async_trait;
+use std::collections::BTreeMap;
 use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
 use tokio_util::sync::CancellationToken;
 
-use crate::interfaces::{AppError, DucklakeConfig, Extension, SecretConfig, SqlValue};
+use crate::interfaces::{AppError, DucklakeConfig, Extension, IngestMode, SecretConfig, SqlValue};
 
 #[async_trait]
 pub trait Database: Send + Sync {
+    /// Runs statements that return no rows and returns the number of rows the last one changed.
+    /// Given parameter rows, the last statement runs once per row in a single transaction and
+    /// the changed rows are summed. Cancelling the token interrupts them.
     async fn execute(
         &self, sql: &str,
+        parameter_rows: &[Vec<SqlValue>],
         default_schema: &Option<String>,
-        extensions: &Option<Vec<Extension>>
+        extensions: &Option<Vec<Extension>>,
+        cancel_token: &CancellationToken,
+    ) -> Result<usize>;
+    /// Runs statements in order on one connection in a single transaction, so that a failing
+    /// statement rolls back the ones before it. Cancelling the token interrupts the running
+    /// statement and rolls the transaction back.
+    async fn execute_transaction(
+        &self,
+        statements: &[String],
+        default_schema: &Option<String>,
+        extensions: &Option<Vec<Extension>>,
+        cancel_token: &CancellationToken,
     ) -> Result<()>;
-    async fn get_json(
+    async fn get_record_batches(
         &self,
         sql: &String,
         args: &Option<Vec<SqlValue>>,
+        named_args: &Option<BTreeMap<String, SqlValue>>,
         prepare_sql: &Option<String>,
         default_schema: &Option<String>,
+        timezone: &Option<String>,
         limit: usize,
         extensions: &Option<Vec<Extension>>,
         secrets: &Option<Vec<SecretConfig>>,
         ducklakes: &Option<Vec<DucklakeConfig>>,
         cancel_token: &CancellationToken,
-    ) -> Result<Vec<u8>>;
-    async fn get_arrow(
+    ) -> Result<Vec<RecordBatch>>;
+    /// `prepared` is given when running a registered statement: the statement stays prepared on
+    /// the connection, and without `prepare_sql` or `timezone`, which may change its result types,
+    /// its result is streamed without probing the schema first.
+    async fn stream_record_batches(
         &self,
         sql: &String,
         args: &Option<Vec<SqlValue>>,
+        named_args: &Option<BTreeMap<String, SqlValue>>,
         prepare_sql: &Option<String>,
         default_schema: &Option<String>,
+        timezone: &Option<String>,
         limit: usize,
         extensions: &Option<Vec<Extension>>,
         secrets: &Option<Vec<SecretConfig>>,
         ducklakes: &Option<Vec<DucklakeConfig>>,
+        prepared: Option<PreparedStatement>,
         cancel_token: &CancellationToken,
-    ) -> Result<Vec<u8>>;
-    async fn get_record_batches(
+    ) -> Result<RecordBatchStream>;
+    /// Runs a query once per row of the incoming parameter batches on a single connection.
+    /// Result batches lead with a `parameter_row` column holding the row's index.
+    async fn stream_parameter_batches(
         &self,
         sql: &String,
-        args: &Option<Vec<SqlValue>>,
         prepare_sql: &Option<String>,
         default_schema: &Option<String>,
+        timezone: &Option<String>,
         limit: usize,
         extensions: &Option<Vec<Extension>>,
         secrets: &Option<Vec<SecretConfig>>,
         ducklakes: &Option<Vec<DucklakeConfig>>,
+        parameters: mpsc::Receiver<Result<RecordBatch>>,
         cancel_token: &CancellationToken,
-    ) -> Result<Vec<RecordBatch>>;
+    ) -> Result<RecordBatchStream>;
+    async fn get_schema(
+        &self,
+        sql: &String,
+        args: &Option<Vec<SqlValue>>,
+        named_args: &Option<BTreeMap<String, SqlValue>>,
+        prepare_sql: &Option<String>,
+        default_schema: &Option<String>,
+        timezone: &Option<String>,
+        extensions: &Option<Vec<Extension>>,
+        secrets: &Option<Vec<SecretConfig>>,
+        ducklakes: &Option<Vec<DucklakeConfig>>,
+    ) -> Result<SchemaRef>;
+    /// Writes batches into `schema.table` through the Arrow appender in a single
+    /// transaction and returns the number of rows written. Appended batches are matched to
+    /// the table's columns by name. An error received on the channel rolls the whole
+    /// ingestion back.
+    async fn append_record_batches(
+        &self,
+        schema: &str,
+        table: &str,
+        mode: IngestMode,
+        batches: mpsc::Receiver<Result<RecordBatch>>,
+    ) -> Result<usize>;
+    /// Writes the rows of `source`, a DuckDB table function call such as `read_csv('...')`,
+    /// into `schema.table` and returns the number of rows written.
+    async fn load_table(&self, schema: &str, table: &str, mode: IngestMode, source: &str) -> Result<usize>;
+    /// Registers a named statement for the database, replacing any statement of that name.
+    /// Pooled connections keep registered statements prepared once they have run them.
+    /// Statements outlive pool resets: new connections prepare them again when they first run them.
+    async fn prepare_statement(
+        &self,
+        name: &str,
+        sql: &str,
+        default_schema: &Option<String>,
+    ) -> Result<PreparedStatement>;
+    /// Looks up a registered statement. Its schema is probed again after a pool reset, since a
+    /// write may have changed what the statement returns.
+    async fn get_prepared_statement(&self, name: &str) -> Result<Option<PreparedStatement>>;
+    /// Names of the parameters of a single statement, in order, as DuckDB reports them.
+    async fn get_parameter_names(&self, sql: &str, default_schema: &Option<String>) -> Result<Vec<String>>;
+    /// Opens a connection of its own to the database, for a session that outlives a request.
+    fn connect(&self) -> Result<duckdb::Connection>;
     fn reconnect(&self) -> Result<()>;
     fn status(&self) -> Result<PoolStatus, AppError>;
     fn kill_all_connections(&self) -> Result<()>;
 }
 
+/// Result batches of a running query. They arrive through a bounded channel, so a
+/// slow consumer holds DuckDB back instead of buffering the whole result.
+pub struct RecordBatchStream {
+    pub schema: SchemaRef,
+    pub batches: ReceiverStream<Result<RecordBatch>>,
+}
+
+/// A statement registered with `Database::prepare_statement`.
+#[derive(Debug, Clone)]
+pub struct PreparedStatement {
+    pub name: String,
+    pub sql: String,
+    pub default_schema: Option<String>,
+    pub parameter_count: usize,
+    pub schema: SchemaRef,
+    /// Set when the pool was reset after the schema was probed.
+    pub(crate) schema_outdated: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct PoolStatus {
     pub db_path: String,
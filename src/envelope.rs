@@ -0,0 +1,183 @@
+use arrow::{datatypes::SchemaRef, record_batch::RecordBatch};
+use arrow_ipc::reader::{FileReader, StreamReader};
+use axum::body::Bytes;
+use futures::{StreamExt, TryStreamExt};
+use serde::Serialize;
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::constants::MAX_CACHED_RESULT_BYTES;
+use crate::db::RecordBatchStream;
+use crate::interfaces::{AppError, ArrowFormat, Command, DbState, QueryParams, QueryResponse, ResultMetadata};
+use crate::json::JsonWriter;
+use crate::query::{RunningQueryGuard, encode_batches, keep_for_cache, take_rows};
+
+/// Schema metadata keys of an `arrow` result sent with an envelope.
+const ROW_COUNT_KEY: &str = "row_count";
+const TRUNCATED_KEY: &str = "truncated";
+
+#[derive(Serialize)]
+struct Column {
+    name: String,
+    #[serde(rename = "type")]
+    data_type: String,
+}
+
+fn columns_json(schema: &SchemaRef) -> anyhow::Result<String> {
+    let columns: Vec<Column> = schema
+        .fields()
+        .iter()
+        .map(|field| Column {
+            name: field.name().clone(),
+            data_type: field.data_type().to_string(),
+        })
+        .collect();
+
+    Ok(serde_json::to_string(&columns)?)
+}
+
+/// The end of a `json` envelope. It differs from one response to the next, so it is left out
+/// of the cached body.
+fn json_trailer(started: Instant, query_id: &str, cached: bool) -> String {
+    format!(
+        ",\"elapsed_ms\":{},\"query_id\":{},\"cached\":{}}}",
+        started.elapsed().as_millis(),
+        serde_json::Value::from(query_id),
+        cached
+    )
+}
+
+/// Sends a result of an `envelope` query as it comes out of the database. `limit` is given when
+/// the server limited the query, to `limit + 1` rows so that the extra row tells whether the
+/// result was cut off; a query with a LIMIT of its own is sent whole. A `json` result is streamed as
+/// `{"columns": [...], "rows": [...], "row_count", "truncated", "elapsed_ms", "query_id", "cached"}`.
+/// An `arrow` result is collected first, since its row count and truncation go into headers
+/// and into the schema metadata. With `cache`, the result is cached under the given key.
+pub(crate) async fn respond(
+    command: &Command,
+    params: &QueryParams,
+    result: RecordBatchStream,
+    limit: Option<usize>,
+    cache: Option<(Arc<DbState>, String)>,
+    guard: RunningQueryGuard,
+    started: Instant,
+) -> Result<QueryResponse, AppError> {
+    let RecordBatchStream { schema, mut batches } = result;
+
+    match command {
+        Command::Json => {
+            let head = format!("{{\"columns\":{},\"rows\":", columns_json(&schema)?);
+            let mut writer = JsonWriter::new(&schema, params)?;
+
+            let stream = async_stream::try_stream! {
+                let guard = guard;
+                let mut cached = cache.as_ref().map(|_| Vec::new());
+                let mut rows = 0;
+                let mut truncated = false;
+
+                keep_for_cache(&mut cached, head.as_bytes());
+                yield Bytes::from(head);
+
+                while let Some(batch) = batches.next().await.transpose()? {
+                    let length = limit.map_or(batch.num_rows(), |limit| batch.num_rows().min(limit - rows));
+                    truncated |= length < batch.num_rows();
+                    rows += length;
+                    writer.write(&batch.slice(0, length))?;
+
+                    let chunk = std::mem::take(writer.get_mut());
+                    if !chunk.is_empty() {
+                        keep_for_cache(&mut cached, &chunk);
+                        yield Bytes::from(chunk);
+                    }
+                }
+                writer.finish()?;
+
+                let mut tail = std::mem::take(writer.get_mut());
+                tail.extend_from_slice(format!(",\"row_count\":{},\"truncated\":{}", rows, truncated).as_bytes());
+                keep_for_cache(&mut cached, &tail);
+                yield Bytes::from(tail);
+
+                if let (Some((db_state, key)), Some(body)) = (cache, cached) {
+                    db_state.cache.lock().await.put(key, body);
+                }
+
+                yield Bytes::from(json_trailer(started, &guard.query_id, false));
+            };
+
+            Ok(QueryResponse::JsonStream(Box::pin(stream)))
+        }
+        Command::Arrow => {
+            let batches: Vec<RecordBatch> = batches.try_collect().await?;
+            let rows: usize = batches.iter().map(|batch| batch.num_rows()).sum();
+            let row_count = limit.map_or(rows, |limit| rows.min(limit));
+            let truncated = row_count < rows;
+
+            let mut metadata = schema.metadata().clone();
+            metadata.insert(ROW_COUNT_KEY.to_string(), row_count.to_string());
+            metadata.insert(TRUNCATED_KEY.to_string(), truncated.to_string());
+            let schema = Arc::new(schema.as_ref().clone().with_metadata(metadata));
+
+            let batches = take_rows(&batches, row_count)
+                .into_iter()
+                .map(|batch| batch.with_schema(Arc::clone(&schema)))
+                .collect::<Result<Vec<_>, _>>()?;
+            let body = encode_batches(command, params, &schema, &batches)?;
+
+            if let Some((db_state, key)) = cache.filter(|_| body.len() <= MAX_CACHED_RESULT_BYTES) {
+                db_state.cache.lock().await.put(key, body.clone());
+            }
+
+            Ok(QueryResponse::WithMetadata {
+                metadata: ResultMetadata {
+                    row_count,
+                    truncated,
+                    elapsed_ms: started.elapsed().as_millis() as u64,
+                    cached: false,
+                },
+                result: Box::new(QueryResponse::Arrow(body, params.arrow_format.unwrap_or_default())),
+            })
+        }
+        _ => Err(anyhow::anyhow!("{:?} results have no envelope", command).into()),
+    }
+}
+
+/// Sends a cached result of an `envelope` query written by [`respond`].
+pub(crate) fn from_cache(
+    command: &Command,
+    params: &QueryParams,
+    query_id: &str,
+    mut body: Vec<u8>,
+    started: Instant,
+) -> Result<QueryResponse, AppError> {
+    match command {
+        Command::Json => {
+            body.extend_from_slice(json_trailer(started, query_id, true).as_bytes());
+            Ok(QueryResponse::Json(String::from_utf8(body)?))
+        }
+        Command::Arrow => {
+            let format = params.arrow_format.unwrap_or_default();
+            let schema = match format {
+                ArrowFormat::Stream => StreamReader::try_new(body.as_slice(), None)?.schema(),
+                ArrowFormat::File => FileReader::try_new(Cursor::new(body.as_slice()), None)?.schema(),
+            };
+            let value = |key: &str| {
+                schema
+                    .metadata()
+                    .get(key)
+                    .ok_or_else(|| anyhow::anyhow!("Cached result has no {} metadata", key))
+            };
+
+            Ok(QueryResponse::WithMetadata {
+                metadata: ResultMetadata {
+                    row_count: value(ROW_COUNT_KEY)?.parse()?,
+                    truncated: value(TRUNCATED_KEY)?.parse()?,
+                    elapsed_ms: started.elapsed().as_millis() as u64,
+                    cached: true,
+                },
+                result: Box::new(QueryResponse::Arrow(body, format)),
+            })
+        }
+        _ => Err(anyhow::anyhow!("{:?} results have no envelope", command).into()),
+    }
+}
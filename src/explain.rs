@@ -0,0 +1,175 @@
+use arrow::array::AsArray;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+use crate::interfaces::{AppError, DbState, QueryParams, QueryResponse};
+use crate::sql::{enforce_query_limit, needs_query_limit, schema_probe_sql};
+
+#[derive(Serialize)]
+struct QueryPlan {
+    analyze: bool,
+    /// Wall time of an analyzed run.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    elapsed_ms: Option<f64>,
+    plan: Vec<PlanOperator>,
+}
+
+#[derive(Serialize)]
+struct PlanOperator {
+    operator: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    estimated_cardinality: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cardinality: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rows_scanned: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timing_ms: Option<f64>,
+    /// DuckDB's operator details, e.g. the table, filters and projections of a scan.
+    details: Map<String, Value>,
+    children: Vec<PlanOperator>,
+}
+
+/// Explains a query with DuckDB's JSON plan output and returns the plan as a tree of
+/// operators. With `analyze`, the query runs and the tree reports actual cardinalities and
+/// timings. The query gets the same arguments, settings and row limit as any other.
+pub async fn run(
+    db_state: &Arc<DbState>,
+    params: &QueryParams,
+    sql: &str,
+    limit: usize,
+    cancel_token: &CancellationToken,
+) -> Result<QueryResponse, AppError> {
+    let analyze = params.analyze.unwrap_or(false);
+
+    let start = Instant::now();
+    let plan = explain(db_state, params, &enforce_query_limit(sql, limit)?, limit, analyze, cancel_token).await?;
+    let elapsed = start.elapsed();
+
+    let plan = QueryPlan {
+        analyze,
+        elapsed_ms: analyze.then_some(elapsed.as_secs_f64() * 1000.0),
+        plan,
+    };
+
+    Ok(QueryResponse::Json(serde_json::to_string(&plan)?))
+}
+
+/// DuckDB's estimate of the rows a query returns, capped at the row limit the server adds.
+/// Limits carry no estimate, so it is the first one found going down from the root. The query
+/// is explained without the added limit, which DuckDB may plan in ways it estimates poorly.
+pub async fn estimated_rows(
+    db_state: &Arc<DbState>,
+    params: &QueryParams,
+    sql: &str,
+    limit: usize,
+    cancel_token: &CancellationToken,
+) -> Result<Option<u64>, AppError> {
+    let plan = explain(db_state, params, sql, limit, false, cancel_token).await?;
+
+    let mut operator = plan.first();
+    while let Some(current) = operator {
+        if let Some(rows) = current.estimated_cardinality {
+            return Ok(Some(if needs_query_limit(sql) { rows.min(limit as u64) } else { rows }));
+        }
+        operator = current.children.first();
+    }
+
+    Ok(None)
+}
+
+async fn explain(
+    db_state: &Arc<DbState>,
+    params: &QueryParams,
+    sql: &str,
+    limit: usize,
+    analyze: bool,
+    cancel_token: &CancellationToken,
+) -> Result<Vec<PlanOperator>, AppError> {
+    if schema_probe_sql(sql).is_none() {
+        return Err(AppError::BadRequest(anyhow::anyhow!("Only a single query can be explained").into()));
+    }
+
+    let options = if analyze { "ANALYZE, FORMAT JSON" } else { "FORMAT JSON" };
+    let explain_sql = format!("EXPLAIN ({}) {}", options, sql);
+
+    let batches = db_state
+        .db
+        .get_record_batches(
+            &explain_sql,
+            &params.args,
+            &params.named_args,
+            &params.prepare_sql,
+            &params.default_schema,
+            &params.timezone,
+            limit,
+            &params.extensions,
+            &params.secrets,
+            &params.ducklakes,
+            cancel_token,
+        )
+        .await?;
+
+    // The plan is the second column, `explain_value`, as a JSON document
+    let mut plan = Vec::new();
+    for batch in &batches {
+        let values = batch
+            .column(1)
+            .as_string_opt::<i32>()
+            .ok_or_else(|| anyhow::anyhow!("Unexpected EXPLAIN output"))?;
+
+        for value in values.iter().flatten() {
+            let roots = match serde_json::from_str(value)? {
+                Value::Array(roots) => roots,
+                Value::Object(mut root) => match root.remove("children") {
+                    Some(Value::Array(roots)) => roots,
+                    _ => Vec::new(),
+                },
+                _ => Vec::new(),
+            };
+            plan.extend(roots.iter().flat_map(operators));
+        }
+    }
+
+    Ok(plan)
+}
+
+/// The operators of a node of DuckDB's plan. The operator DuckDB adds for `EXPLAIN ANALYZE`
+/// itself is left out in favour of its children.
+fn operators(node: &Value) -> Vec<PlanOperator> {
+    let children = || -> Vec<PlanOperator> {
+        node["children"]
+            .as_array()
+            .map(|children| children.iter().flat_map(operators).collect())
+            .unwrap_or_default()
+    };
+
+    if node["operator_type"] == "EXPLAIN_ANALYZE" {
+        return children();
+    }
+
+    let mut details = node["extra_info"].as_object().cloned().unwrap_or_default();
+    let estimated_cardinality = details
+        .remove("Estimated Cardinality")
+        .and_then(|cardinality| cardinality.as_str()?.parse().ok());
+
+    let operator = PlanOperator {
+        operator: node["operator_name"]
+            .as_str()
+            .or(node["name"].as_str())
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+        estimated_cardinality,
+        cardinality: node["operator_cardinality"].as_u64(),
+        rows_scanned: node["operator_rows_scanned"].as_u64(),
+        timing_ms: node["operator_timing"].as_f64().map(|seconds| seconds * 1000.0),
+        details,
+        children: children(),
+    };
+
+    vec![operator]
+}
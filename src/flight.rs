@@ -1,156 +1,867 @@
-use crate::{interfaces::QueryParams, state::AppState};
+#![allow(clippy::result_large_err)]
+
+use crate::{
+    auth::{AuthConfig, authorize_flight, basic_auth_password, flight_auth_interceptor, validate_auth_token},
+    constants::{MEMORY_DB_PATH, RECORD_BATCH_CHANNEL_CAPACITY},
+    db::RecordBatchStream,
+    explain,
+    flight_sql::{FLIGHT_SQL_ACTIONS, FLIGHT_SQL_TYPE_URL_PREFIX, FlightSqlServer},
+    interfaces::{IngestMode, QueryInfo, QueryParams, QueryResponse},
+    query::{self, RunningQueryGuard},
+    sql::quote_identifier,
+    state::{AppState, FlightTicket, FlightTicketStatus},
+};
+use arrow::{
+    array::{Array, ArrayRef, AsArray, StringArray},
+    datatypes::{Int64Type, SchemaRef},
+    error::ArrowError,
+    ipc::writer::IpcWriteOptions,
+    record_batch::RecordBatch,
+};
 use arrow_flight::{
-    Action, ActionType, Criteria, Empty, FlightData, FlightDescriptor, FlightInfo, HandshakeRequest, HandshakeResponse,
-    PollInfo, PutResult, SchemaResult, Ticket, encode::FlightDataEncoderBuilder, error::FlightError,
+    Action, ActionType, Criteria, Empty, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo, HandshakeRequest,
+    HandshakeResponse, PollInfo, PutResult, SchemaAsIpc, SchemaResult, Ticket,
+    decode::{DecodedPayload, FlightDataDecoder},
+    encode::FlightDataEncoderBuilder,
+    error::FlightError,
     flight_service_server::FlightService, flight_service_server::FlightServiceServer,
+    sql::{Any, Command},
 };
-use futures::{TryStreamExt, stream::BoxStream};
-use std::{net::SocketAddr, sync::Arc};
+use axum::http::header::AUTHORIZATION;
+use futures::{Stream, StreamExt, TryStreamExt, stream::BoxStream};
+use prost::Message;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 use tonic::{Request, Response, Status, Streaming, transport::Server};
+use uuid::Uuid;
+
+/// DuckDB database files carry this magic after an 8-byte checksum.
+const DUCKDB_FILE_MAGIC: &[u8] = b"DUCK";
 
 pub struct FlightServer {
     pub state: Arc<AppState>,
+    auth_config: Option<AuthConfig>,
+    sql: FlightSqlServer,
 }
 
 impl FlightServer {
-    pub fn new(state: Arc<AppState>) -> Self {
-        Self { state }
+    pub fn new(state: Arc<AppState>, auth_config: Option<AuthConfig>) -> Self {
+        let sql = FlightSqlServer::new(Arc::clone(&state));
+        Self { state, auth_config, sql }
     }
 }
 
-#[tonic::async_trait]
-impl FlightService for FlightServer {
-    type HandshakeStream = BoxStream<'static, Result<HandshakeResponse, Status>>;
-    type ListFlightsStream = BoxStream<'static, Result<FlightInfo, Status>>;
-    type DoGetStream = BoxStream<'static, Result<FlightData, Status>>;
-    type DoPutStream = BoxStream<'static, Result<PutResult, Status>>;
-    type DoActionStream = BoxStream<'static, Result<arrow_flight::Result, Status>>;
-    type ListActionsStream = BoxStream<'static, Result<ActionType, Status>>;
-    type DoExchangeStream = BoxStream<'static, Result<FlightData, Status>>;
+const ADMIN_ACTIONS: &[(&str, &str)] = &[
+    ("healthcheck", "Health check action"),
+    ("cancel_query", "Cancel a running query, the body is the query ID"),
+    ("list_queries", "List running queries as JSON, or as Arrow IPC when the body is \"arrow\""),
+    ("killall", "Cancel all running queries and interrupt every connection"),
+    ("killall_database", "Cancel the queries of a database and interrupt its connections, the body is the database"),
+    ("status", "Connection pool status and running queries as JSON"),
+    ("reconnect", "Reopen the connection pool of a database, the body is the database"),
+];
 
-    async fn do_get(&self, request: Request<Ticket>) -> Result<Response<Self::DoGetStream>, Status> {
-        let ticket_bytes = request.into_inner().ticket;
+/// Decodes a ticket or descriptor command as a Flight SQL message, if it is one.
+/// Anything else is treated as one of our own JSON-encoded payloads.
+fn flight_sql_command(bytes: &[u8]) -> Option<Command> {
+    let any = Any::decode(bytes).ok()?;
+    if !any.type_url.starts_with(FLIGHT_SQL_TYPE_URL_PREFIX) {
+        return None;
+    }
+    Command::try_from(any).ok()
+}
 
-        let params: QueryParams = serde_json::from_slice(&ticket_bytes)
-            .map_err(|e| Status::invalid_argument(format!("Invalid ticket JSON: {}", e)))?;
+pub(crate) async fn query_flight_data(
+    state: &Arc<AppState>,
+    params: &QueryParams,
+) -> Result<BoxStream<'static, Result<FlightData, Status>>, Status> {
+    let db_state = state
+        .get_or_create_db_state(
+            &params.database,
+            &params.extensions,
+            &params.secrets,
+            &params.ducklakes
+        )
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
 
-        tracing::info!("Flight QueryParams: {:?}", params);
+    let sql = required_sql(params)?;
 
-        let db_state = self.state
-            .get_or_create_db_state(
-                &params.database,
-                &params.extensions,
-                &params.secrets,
-                &params.ducklakes
-            )
-            .await
-            .map_err(|e| Status::internal(e.to_string()))?;
+    let limit = params.limit.unwrap_or(state.defaults.row_limit);
+    let (query_id, cancel_token) = state.start_query(params.database.clone(), sql.clone()).await;
 
-        let sql = params
-            .sql
-            .ok_or_else(|| Status::invalid_argument("SQL query is required"))?;
+    // The guard lives as long as the response stream, so the query stays listed in
+    // running_queries while batches are still being sent.
+    let guard = RunningQueryGuard {
+        state: Arc::clone(state),
+        query_id,
+        cancel_token: cancel_token.clone(),
+    };
 
-        if sql.trim().is_empty() {
-            return Err(Status::invalid_argument("SQL query cannot be empty"));
+    let result = db_state
+        .db
+        .stream_record_batches(
+            &sql,
+            &params.args,
+            &params.named_args,
+            &params.prepare_sql,
+            &params.default_schema,
+            &params.timezone,
+            limit,
+            &params.extensions,
+            &params.secrets,
+            &params.ducklakes,
+            None,
+            &cancel_token
+        )
+        .await;
+
+    let RecordBatchStream { schema, batches } = result.map_err(|e| Status::internal(e.to_string()))?;
+
+    let stream = FlightDataEncoderBuilder::new()
+        .with_schema(schema)
+        .build(batches.map_err(|e| FlightError::ExternalError(e.into())))
+        .map_err(Status::from)
+        .inspect(move |_| {
+            let _ = &guard;
+        });
+
+    Ok(Box::pin(stream))
+}
+
+/// Writes a `do_put` stream into the table named by a path descriptor of the form
+/// `[database, schema, table]` or `[database, schema, table, mode]`.
+pub(crate) async fn ingest_flight_data<S>(state: &AppState, path: &[String], stream: S) -> Result<PutResult, Status>
+where
+    S: Stream<Item = Result<FlightData, Status>> + Send + Unpin + 'static,
+{
+    let (database, schema, table, mode) = match path {
+        [database, schema, table] => (database, schema, table, IngestMode::default()),
+        [database, schema, table, mode] => (
+            database,
+            schema,
+            table,
+            mode.parse::<IngestMode>().map_err(Status::invalid_argument)?,
+        ),
+        _ => {
+            return Err(Status::invalid_argument(
+                "Ingestion path must be [database, schema, table] or [database, schema, table, mode]",
+            ));
         }
+    };
+
+    let db_state = state
+        .get_or_create_db_state(database, &None, &None, &None)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    let (tx, rx) = mpsc::channel(RECORD_BATCH_CHANNEL_CAPACITY);
+
+    let (rows, ()) = tokio::join!(
+        db_state.db.append_record_batches(schema, table, mode, rx),
+        forward_record_batches(stream, tx)
+    );
+    let rows = rows.map_err(|e| Status::internal(e.to_string()))?;
+
+    db_state.cache.lock().await.clear();
+
+    tracing::info!("Ingested {} rows into {}.{}.{}", rows, database, schema, table);
+
+    Ok(PutResult {
+        app_metadata: serde_json::to_vec(&serde_json::json!({ "rows": rows }))
+            .map_err(|e| Status::internal(e.to_string()))?
+            .into(),
+    })
+}
+
+/// Runs the query named by the descriptor of the first `do_exchange` message once for every
+/// row of the parameter batches that follow. Each result row carries the index of its parameter
+/// row in a leading `parameter_row` column.
+pub(crate) async fn exchange_flight_data(
+    state: &Arc<AppState>,
+    mut stream: Streaming<FlightData>,
+) -> Result<BoxStream<'static, Result<FlightData, Status>>, Status> {
+    let first = stream
+        .message()
+        .await?
+        .ok_or_else(|| Status::invalid_argument("Exchange stream is empty"))?;
+    let descriptor = first
+        .flight_descriptor
+        .as_ref()
+        .ok_or_else(|| Status::invalid_argument("First exchange message must carry a flight descriptor"))?;
+    let params = descriptor_params(descriptor)?;
 
-        let limit = params.limit.unwrap_or(self.state.defaults.row_limit);
-        let (query_id, cancel_token) = self.state.start_query(params.database.clone(), sql.clone()).await;
+    let db_state = state
+        .get_or_create_db_state(
+            &params.database,
+            &params.extensions,
+            &params.secrets,
+            &params.ducklakes
+        )
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    let sql = required_sql(&params)?;
+
+    let limit = params.limit.unwrap_or(state.defaults.row_limit);
+    let (query_id, cancel_token) = state.start_query(params.database.clone(), sql.clone()).await;
+
+    let guard = RunningQueryGuard {
+        state: Arc::clone(state),
+        query_id,
+        cancel_token: cancel_token.clone(),
+    };
+
+    // The first message may already hold the parameter schema, so it is decoded with the rest
+    let (tx, rx) = mpsc::channel(RECORD_BATCH_CHANNEL_CAPACITY);
+    let parameters = futures::stream::iter([Ok(first)]).chain(stream);
+    tokio::spawn(forward_record_batches(parameters, tx));
+
+    let result = db_state
+        .db
+        .stream_parameter_batches(
+            &sql,
+            &params.prepare_sql,
+            &params.default_schema,
+            &params.timezone,
+            limit,
+            &params.extensions,
+            &params.secrets,
+            &params.ducklakes,
+            rx,
+            &cancel_token
+        )
+        .await;
+
+    let RecordBatchStream { schema, batches } = result.map_err(|e| Status::internal(e.to_string()))?;
+
+    let stream = FlightDataEncoderBuilder::new()
+        .with_schema(schema)
+        .build(batches.map_err(|e| FlightError::ExternalError(e.into())))
+        .map_err(Status::from)
+        .inspect(move |_| {
+            let _ = &guard;
+        });
+
+    Ok(Box::pin(stream))
+}
+
+/// Decodes incoming Flight data into record batches for a consumer on the other end of `tx`.
+/// A schema message is forwarded as an empty batch so consumers see it even without rows.
+async fn forward_record_batches<S>(stream: S, tx: mpsc::Sender<anyhow::Result<RecordBatch>>)
+where
+    S: Stream<Item = Result<FlightData, Status>> + Send + Unpin + 'static,
+{
+    let mut decoder = FlightDataDecoder::new(stream.map_err(FlightError::from));
+    loop {
+        let batch = match decoder.try_next().await {
+            Ok(Some(data)) => match data.payload {
+                DecodedPayload::Schema(schema) => Ok(RecordBatch::new_empty(schema)),
+                DecodedPayload::RecordBatch(batch) => Ok(batch),
+                DecodedPayload::None => continue,
+            },
+            Ok(None) => break,
+            Err(e) => Err(anyhow::anyhow!("Failed to decode flight data: {}", e)),
+        };
+        let failed = batch.is_err();
+        // A closed channel means the consumer already failed and reports its own error
+        if tx.send(batch).await.is_err() || failed {
+            break;
+        }
+    }
+}
+
+fn descriptor_params(descriptor: &FlightDescriptor) -> Result<QueryParams, Status> {
+    serde_json::from_slice(&descriptor.cmd)
+        .map_err(|e| Status::invalid_argument(format!("Invalid descriptor JSON: {}", e)))
+}
+
+fn required_sql(params: &QueryParams) -> Result<String, Status> {
+    let sql = params
+        .sql
+        .clone()
+        .ok_or_else(|| Status::invalid_argument("SQL query is required"))?;
+
+    if sql.trim().is_empty() {
+        return Err(Status::invalid_argument("SQL query cannot be empty"));
+    }
+
+    Ok(sql)
+}
+
+/// Resolves a ticket or descriptor command holding a server-issued ticket ID. Anything
+/// that is not shaped like one is left to the JSON protocol.
+async fn lookup_flight_ticket(state: &AppState, bytes: &[u8]) -> Result<Option<FlightTicket>, Status> {
+    let Some(id) = std::str::from_utf8(bytes).ok().filter(|id| Uuid::parse_str(id).is_ok()) else {
+        return Ok(None);
+    };
+
+    match state.get_flight_ticket(id).await {
+        Some(ticket) => Ok(Some(ticket)),
+        None => Err(Status::not_found(format!("Flight ticket {} is unknown or expired", id))),
+    }
+}
+
+async fn query_schema(state: &AppState, params: &QueryParams) -> Result<SchemaRef, Status> {
+    let db_state = state
+        .get_or_create_db_state(&params.database, &params.extensions, &params.secrets, &params.ducklakes)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    let sql = required_sql(params)?;
+
+    db_state
+        .db
+        .get_schema(
+            &sql,
+            &params.args,
+            &params.named_args,
+            &params.prepare_sql,
+            &params.default_schema,
+            &params.timezone,
+            &params.extensions,
+            &params.secrets,
+            &params.ducklakes,
+        )
+        .await
+        .map_err(|e| Status::internal(e.to_string()))
+}
+
+fn ticket_endpoint(ticket: &FlightTicket) -> FlightEndpoint {
+    FlightEndpoint::new()
+        .with_ticket(Ticket::new(ticket.id.clone()))
+        .with_expiration_time(ticket.expires_at.into())
+}
+
+fn poll_info(ticket: &FlightTicket) -> Result<PollInfo, Status> {
+    let poll_info = PollInfo::new().with_expiration_time(ticket.expires_at.into());
+
+    match &ticket.status {
+        FlightTicketStatus::Running { .. } => Ok(poll_info.with_descriptor(FlightDescriptor::new_cmd(ticket.id.clone()))),
+        _ => poll_info
+            .with_info(polled_flight_info(ticket)?)
+            .try_with_progress(1.0)
+            .map_err(|e| Status::internal(e.to_string())),
+    }
+}
+
+/// The `FlightInfo` of a polled query that has completed, with the size of its result.
+fn polled_flight_info(ticket: &FlightTicket) -> Result<FlightInfo, Status> {
+    match &ticket.status {
+        FlightTicketStatus::Ready { schema, batches } => Ok(FlightInfo::new()
+            .try_with_schema(schema)
+            .map_err(|e| Status::internal(e.to_string()))?
+            .with_endpoint(ticket_endpoint(ticket))
+            .with_descriptor(FlightDescriptor::new_cmd(ticket.id.clone()))
+            .with_total_records(batches.iter().map(|batch| batch.num_rows() as i64).sum())
+            .with_total_bytes(batches.iter().map(|batch| batch.get_array_memory_size() as i64).sum())),
+        FlightTicketStatus::Running { .. } => Err(Status::unavailable("Query is still running, poll until it completes")),
+        FlightTicketStatus::Failed(error) => Err(Status::internal(error.clone())),
+        FlightTicketStatus::Deferred => Err(Status::invalid_argument(format!(
+            "Flight ticket {} was not issued by poll_flight_info",
+            ticket.id
+        ))),
+    }
+}
+
+/// DuckDB's estimate of the rows a query returns, or -1 when it has none.
+async fn query_row_estimate(state: &AppState, params: &QueryParams) -> Result<i64, Status> {
+    let db_state = state
+        .get_or_create_db_state(&params.database, &params.extensions, &params.secrets, &params.ducklakes)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    let sql = required_sql(params)?;
+    let limit = params.limit.unwrap_or(state.defaults.row_limit);
+
+    let rows = explain::estimated_rows(&db_state, params, &sql, limit, &CancellationToken::new()).await?;
+    Ok(rows.map_or(-1, |rows| rows as i64))
+}
+
+/// The UTF-8 argument of an admin action, e.g. the query ID for `cancel_query`.
+fn action_argument(action: &Action) -> Result<String, Status> {
+    let argument = std::str::from_utf8(&action.body)
+        .map_err(|_| Status::invalid_argument("Action body must be UTF-8"))?
+        .trim();
+
+    if argument.is_empty() {
+        return Err(Status::invalid_argument(format!("Action '{}' requires a body", action.r#type)));
+    }
+
+    Ok(argument.to_string())
+}
+
+async fn action_body(response: QueryResponse) -> Result<Vec<u8>, Status> {
+    response.into_body().await.map_err(|e| Status::internal(e.to_string()))
+}
+
+fn running_queries_arrow(queries: &[QueryInfo]) -> Result<Vec<u8>, ArrowError> {
+    let column = |value: fn(&QueryInfo) -> &str| -> ArrayRef {
+        Arc::new(StringArray::from_iter_values(queries.iter().map(value)))
+    };
+    let batch = RecordBatch::try_from_iter(vec![
+        ("id", column(|query| &query.id)),
+        ("database", column(|query| &query.database)),
+        ("sql", column(|query| &query.sql)),
+        ("started_at", column(|query| &query.started_at)),
+    ])?;
+
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut writer = arrow_ipc::writer::FileWriter::try_new(&mut buffer, batch.schema().as_ref())?;
+    writer.write(&batch)?;
+    writer.finish()?;
+    drop(writer);
+
+    Ok(buffer)
+}
+
+/// Database IDs of the DuckDB files under the root, followed by the in-memory databases
+/// the server has open.
+async fn list_databases(state: &AppState) -> Result<Vec<String>, Status> {
+    let root = PathBuf::from(&state.root);
+    let mut databases = tokio::task::spawn_blocking(move || database_files(&root))
+        .await
+        .map_err(|e| Status::internal(format!("Task error: {}", e)))?;
+    databases.sort();
+
+    let mut memory_databases: Vec<String> = state
+        .states
+        .lock()
+        .await
+        .keys()
+        .filter(|database| database.starts_with(MEMORY_DB_PATH))
+        .cloned()
+        .collect();
+    memory_databases.sort();
+    databases.extend(memory_databases);
+
+    Ok(databases)
+}
+
+/// Paths relative to `root` of the DuckDB files below it, recognized by their header. Hidden
+/// entries, such as the upload and bundle directories, are skipped.
+fn database_files(root: &Path) -> Vec<String> {
+    let pattern = format!("{}/**/*", glob::Pattern::escape(&root.to_string_lossy()));
+    let options = glob::MatchOptions {
+        require_literal_leading_dot: true,
+        ..Default::default()
+    };
+    let Ok(paths) = glob::glob_with(&pattern, options) else {
+        return Vec::new();
+    };
+
+    paths
+        .flatten()
+        .filter(|path| is_duckdb_file(path))
+        .filter_map(|path| path.strip_prefix(root).ok()?.to_str().map(str::to_string))
+        .collect()
+}
+
+fn is_duckdb_file(path: &Path) -> bool {
+    let mut header = [0; 8 + DUCKDB_FILE_MAGIC.len()];
+    File::open(path).and_then(|mut file| file.read_exact(&mut header)).is_ok() && header[8..] == *DUCKDB_FILE_MAGIC
+}
 
-        let result = db_state
+/// One `FlightInfo` per table or view of a database, addressed by a `[database, schema, table]`
+/// path. Its ticket is the JSON query selecting the whole table, so listings leave no state behind.
+async fn database_flights(sql: &FlightSqlServer, database: &str) -> Result<Vec<FlightInfo>, Status> {
+    let schemas = sql
+        .table_schemas(database, &["table_catalog = current_database()"], vec![])
+        .await?;
+    let row_estimates = table_row_estimates(sql, database).await?;
+
+    let mut infos = Vec::new();
+    for ((_, schema, table), table_schema) in schemas {
+        // Views have no estimate of their own
+        let total_records = row_estimates.get(&(schema.clone(), table.clone())).copied().unwrap_or(-1);
+
+        let ticket = serde_json::json!({
+            "database": database,
+            "type": "arrow",
+            "sql": format!("SELECT * FROM {}.{}", quote_identifier(&schema), quote_identifier(&table)),
+        });
+
+        infos.push(
+            FlightInfo::new()
+                .try_with_schema(&table_schema)
+                .map_err(|e| Status::internal(e.to_string()))?
+                .with_endpoint(FlightEndpoint::new().with_ticket(Ticket::new(ticket.to_string())))
+                .with_descriptor(FlightDescriptor::new_path(vec![database.to_string(), schema, table]))
+                .with_total_records(total_records),
+        );
+    }
+
+    Ok(infos)
+}
+
+/// DuckDB's estimated row counts of the tables of a database, keyed by schema and table.
+async fn table_row_estimates(
+    sql: &FlightSqlServer,
+    database: &str,
+) -> Result<HashMap<(String, String), i64>, Status> {
+    let batches = sql
+        .metadata_batches(
+            database,
+            "SELECT schema_name, table_name, estimated_size FROM duckdb_tables() \
+                WHERE database_name = current_database()",
+            vec![],
+        )
+        .await?;
+
+    let mut estimates = HashMap::new();
+    for batch in &batches {
+        let (Some(schemas), Some(tables), Some(sizes)) = (
+            batch.column(0).as_string_opt::<i32>(),
+            batch.column(1).as_string_opt::<i32>(),
+            batch.column(2).as_primitive_opt::<Int64Type>(),
+        )
+        else {
+            return Err(Status::internal("Unexpected duckdb_tables() columns"));
+        };
+
+        for row in 0..batch.num_rows() {
+            if sizes.is_valid(row) {
+                let key = (schemas.value(row).to_string(), tables.value(row).to_string());
+                estimates.insert(key, sizes.value(row));
+            }
+        }
+    }
+
+    Ok(estimates)
+}
+
+/// Runs a polled query to completion and keeps its batches on the ticket until it expires.
+async fn run_polled_query(state: Arc<AppState>, ticket: FlightTicket, query_id: String, cancel_token: CancellationToken) {
+    let params = &ticket.params;
+
+    let result = async {
+        let db_state = state
+            .get_or_create_db_state(&params.database, &params.extensions, &params.secrets, &params.ducklakes)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let sql = required_sql(params)?;
+        let limit = params.limit.unwrap_or(state.defaults.row_limit);
+
+        let RecordBatchStream { schema, batches } = db_state
             .db
-            .get_record_batches(
+            .stream_record_batches(
                 &sql,
                 &params.args,
+                &params.named_args,
                 &params.prepare_sql,
                 &params.default_schema,
+                &params.timezone,
                 limit,
                 &params.extensions,
                 &params.secrets,
                 &params.ducklakes,
+                None,
                 &cancel_token
             )
-            .await;
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
 
-        // Always clean up the query from running_queries, regardless of success or failure
-        {
-            let mut queries = self.state.running_queries.lock().await;
-            queries.remove(&query_id);
+        let batches: Vec<RecordBatch> = batches
+            .try_collect()
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok::<_, Status>((schema, batches))
+    }
+    .await;
+
+    state.running_queries.lock().await.remove(&query_id);
+
+    let status = match result {
+        Ok((schema, batches)) => FlightTicketStatus::Ready {
+            schema,
+            batches: Arc::new(batches),
+        },
+        Err(e) => {
+            tracing::warn!("Polled query {} failed: {}", query_id, e.message());
+            FlightTicketStatus::Failed(e.message().to_string())
         }
+    };
+
+    state.set_flight_ticket_status(&ticket.id, status).await;
+}
+
+#[tonic::async_trait]
+impl FlightService for FlightServer {
+    type HandshakeStream = BoxStream<'static, Result<HandshakeResponse, Status>>;
+    type ListFlightsStream = BoxStream<'static, Result<FlightInfo, Status>>;
+    type DoGetStream = BoxStream<'static, Result<FlightData, Status>>;
+    type DoPutStream = BoxStream<'static, Result<PutResult, Status>>;
+    type DoActionStream = BoxStream<'static, Result<arrow_flight::Result, Status>>;
+    type ListActionsStream = BoxStream<'static, Result<ActionType, Status>>;
+    type DoExchangeStream = BoxStream<'static, Result<FlightData, Status>>;
 
-        let batches = result.map_err(|e| Status::internal(e.to_string()))?;
+    async fn do_get(&self, request: Request<Ticket>) -> Result<Response<Self::DoGetStream>, Status> {
+        authorize_flight(&request)?;
 
-        let schema = batches
-            .first()
-            .ok_or_else(|| Status::internal("No batches returned"))?
-            .schema();
+        if flight_sql_command(&request.get_ref().ticket).is_some() {
+            return FlightService::do_get(&self.sql, request).await;
+        }
 
-        let stream = FlightDataEncoderBuilder::new()
-            .with_schema(schema)
-            .build(futures::stream::iter(batches.into_iter().map(Ok::<_, FlightError>)))
-            .map_err(Status::from);
+        let ticket_bytes = request.into_inner().ticket;
 
-        Ok(Response::new(Box::pin(stream)))
+        if let Some(ticket) = lookup_flight_ticket(&self.state, &ticket_bytes).await? {
+            let stream = match ticket.status {
+                FlightTicketStatus::Deferred => query_flight_data(&self.state, &ticket.params).await?,
+                FlightTicketStatus::Ready { schema, batches } => {
+                    let batches = batches.iter().cloned().map(Ok).collect::<Vec<_>>();
+                    FlightDataEncoderBuilder::new()
+                        .with_schema(schema)
+                        .build(futures::stream::iter(batches))
+                        .map_err(Status::from)
+                        .boxed()
+                }
+                FlightTicketStatus::Running { .. } => {
+                    return Err(Status::unavailable("Query is still running, poll until it completes"));
+                }
+                FlightTicketStatus::Failed(error) => return Err(Status::internal(error)),
+            };
+            return Ok(Response::new(stream));
+        }
+
+        let params: QueryParams = serde_json::from_slice(&ticket_bytes)
+            .map_err(|e| Status::invalid_argument(format!("Invalid ticket JSON: {}", e)))?;
+
+        tracing::info!("Flight QueryParams: {:?}", params);
+
+        let stream = query_flight_data(&self.state, &params).await?;
+        Ok(Response::new(stream))
     }
 
     async fn handshake(
         &self,
-        _request: Request<Streaming<HandshakeRequest>>,
+        request: Request<Streaming<HandshakeRequest>>,
     ) -> Result<Response<Self::HandshakeStream>, Status> {
-        Err(Status::unimplemented("Not implemented"))
+        let Some(config) = self.auth_config.as_ref().filter(|config| config.require_auth) else {
+            let response = HandshakeResponse::default();
+            return Ok(Response::new(Box::pin(futures::stream::once(async { Ok(response) }))));
+        };
+
+        // The token comes either as the password of a Basic header or as the payload
+        // of the first handshake message.
+        let basic_password = request
+            .metadata()
+            .get(AUTHORIZATION.as_str())
+            .and_then(|header| header.to_str().ok())
+            .and_then(basic_auth_password);
+
+        let token = match basic_password {
+            Some(password) => password,
+            None => request
+                .into_inner()
+                .message()
+                .await?
+                .map(|message| String::from_utf8_lossy(&message.payload).into_owned())
+                .unwrap_or_default(),
+        };
+
+        if !validate_auth_token(&token, config) {
+            tracing::warn!("Invalid authentication token in Flight handshake");
+            return Err(Status::unauthenticated("Invalid authentication token"));
+        }
+
+        let bearer = format!("Bearer {}", token)
+            .parse()
+            .map_err(|_| Status::invalid_argument("Authentication token is not valid metadata"))?;
+
+        let response = HandshakeResponse {
+            protocol_version: 0,
+            payload: token.into_bytes().into(),
+        };
+        let mut response = Response::new(Box::pin(futures::stream::once(async { Ok(response) })) as Self::HandshakeStream);
+        response.metadata_mut().insert(AUTHORIZATION.as_str(), bearer);
+        Ok(response)
     }
 
-    async fn list_flights(&self, _request: Request<Criteria>) -> Result<Response<Self::ListFlightsStream>, Status> {
-        Err(Status::unimplemented("Not implemented"))
+    async fn list_flights(&self, request: Request<Criteria>) -> Result<Response<Self::ListFlightsStream>, Status> {
+        authorize_flight(&request)?;
+
+        // The criteria is a database name or a glob over database names; empty lists everything
+        let expression = std::str::from_utf8(&request.get_ref().expression)
+            .map_err(|_| Status::invalid_argument("Criteria must be UTF-8"))?
+            .trim();
+        let pattern = match expression {
+            "" => None,
+            expression => Some(
+                glob::Pattern::new(expression)
+                    .map_err(|e| Status::invalid_argument(format!("Invalid criteria pattern: {}", e)))?,
+            ),
+        };
+
+        let mut infos = Vec::new();
+        for database in list_databases(&self.state).await? {
+            if pattern.as_ref().is_some_and(|pattern| !pattern.matches(&database)) {
+                continue;
+            }
+
+            match database_flights(&self.sql, &database).await {
+                Ok(database_infos) => infos.extend(database_infos),
+                Err(e) => tracing::warn!("Skipping database {} in list_flights: {}", database, e.message()),
+            }
+        }
+
+        Ok(Response::new(Box::pin(futures::stream::iter(infos.into_iter().map(Ok)))))
     }
 
-    async fn get_flight_info(&self, _request: Request<FlightDescriptor>) -> Result<Response<FlightInfo>, Status> {
-        Err(Status::unimplemented("Not implemented"))
+    async fn get_flight_info(&self, request: Request<FlightDescriptor>) -> Result<Response<FlightInfo>, Status> {
+        authorize_flight(&request)?;
+
+        if flight_sql_command(&request.get_ref().cmd).is_some() {
+            return FlightService::get_flight_info(&self.sql, request).await;
+        }
+
+        let descriptor = request.into_inner();
+
+        // The descriptor returned by `poll_flight_info` describes the polled result
+        if let Some(ticket) = lookup_flight_ticket(&self.state, &descriptor.cmd).await? {
+            return Ok(Response::new(polled_flight_info(&ticket)?));
+        }
+
+        let params = descriptor_params(&descriptor)?;
+        let schema = query_schema(&self.state, &params).await?;
+        let total_records = query_row_estimate(&self.state, &params).await?;
+        let ticket = self.state.issue_flight_ticket(params, FlightTicketStatus::Deferred).await;
+
+        let info = FlightInfo::new()
+            .try_with_schema(&schema)
+            .map_err(|e| Status::internal(e.to_string()))?
+            .with_endpoint(ticket_endpoint(&ticket))
+            .with_descriptor(descriptor)
+            .with_total_records(total_records);
+
+        Ok(Response::new(info))
     }
 
-    async fn poll_flight_info(&self, _request: Request<FlightDescriptor>) -> Result<Response<PollInfo>, Status> {
-        Err(Status::unimplemented("Not implemented"))
+    async fn poll_flight_info(&self, request: Request<FlightDescriptor>) -> Result<Response<PollInfo>, Status> {
+        authorize_flight(&request)?;
+
+        let descriptor = request.into_inner();
+
+        // Follow-up polls send back the descriptor returned by the previous poll
+        if let Some(ticket) = lookup_flight_ticket(&self.state, &descriptor.cmd).await? {
+            return Ok(Response::new(poll_info(&ticket)?));
+        }
+
+        let params = descriptor_params(&descriptor)?;
+        let sql = required_sql(&params)?;
+        let (query_id, cancel_token) = self.state.start_query(params.database.clone(), sql).await;
+        let ticket = self
+            .state
+            .issue_flight_ticket(params, FlightTicketStatus::Running { query_id: query_id.clone() })
+            .await;
+
+        tokio::spawn(run_polled_query(Arc::clone(&self.state), ticket.clone(), query_id, cancel_token));
+
+        Ok(Response::new(poll_info(&ticket)?))
     }
 
-    async fn get_schema(&self, _request: Request<FlightDescriptor>) -> Result<Response<SchemaResult>, Status> {
-        Err(Status::unimplemented("Not implemented"))
+    async fn get_schema(&self, request: Request<FlightDescriptor>) -> Result<Response<SchemaResult>, Status> {
+        authorize_flight(&request)?;
+
+        if flight_sql_command(&request.get_ref().cmd).is_some() {
+            return FlightService::get_schema(&self.sql, request).await;
+        }
+
+        let params = descriptor_params(request.get_ref())?;
+        let schema = query_schema(&self.state, &params).await?;
+        let options = IpcWriteOptions::default();
+        let result = SchemaAsIpc::new(&schema, &options)
+            .try_into()
+            .map_err(|e: ArrowError| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(result))
     }
 
-    async fn do_put(&self, _request: Request<Streaming<FlightData>>) -> Result<Response<Self::DoPutStream>, Status> {
-        Err(Status::unimplemented("Not implemented"))
+    async fn do_put(&self, request: Request<Streaming<FlightData>>) -> Result<Response<Self::DoPutStream>, Status> {
+        authorize_flight(&request)?;
+
+        // The first message has to be peeked without losing it, which only the Flight SQL
+        // dispatcher can do. Path descriptors for table ingestion come back through its
+        // fallback and end up in `ingest_flight_data`.
+        FlightService::do_put(&self.sql, request).await
     }
 
     async fn do_exchange(
         &self,
-        _request: Request<Streaming<FlightData>>,
+        request: Request<Streaming<FlightData>>,
     ) -> Result<Response<Self::DoExchangeStream>, Status> {
-        Err(Status::unimplemented("Not implemented"))
+        authorize_flight(&request)?;
+
+        let stream = exchange_flight_data(&self.state, request.into_inner()).await?;
+        Ok(Response::new(stream))
     }
 
     async fn do_action(&self, request: Request<Action>) -> Result<Response<Self::DoActionStream>, Status> {
-        let action = request.into_inner();
-
-        if action.r#type == "healthcheck" {
-            let response = arrow_flight::Result {
-                body: b"healthy".to_vec().into(),
-            };
-            let stream = futures::stream::once(async { Ok(response) });
-            Ok(Response::new(Box::pin(stream)))
+        if request.get_ref().r#type != "healthcheck" {
+            authorize_flight(&request)?;
         }
-        else {
-            Err(Status::unimplemented(format!(
-                "Action '{}' not implemented",
-                action.r#type
-            )))
+
+        if FLIGHT_SQL_ACTIONS.contains(&request.get_ref().r#type.as_str()) {
+            return FlightService::do_action(&self.sql, request).await;
         }
+
+        let action = request.into_inner();
+        let state = &self.state;
+
+        let body = match action.r#type.as_str() {
+            "healthcheck" => b"healthy".to_vec(),
+            "cancel_query" => action_body(query::cancel_query(state, action_argument(&action)?).await?).await?,
+            "list_queries" => match query::list_running_queries(state).await? {
+                QueryResponse::RunningQueries { queries } if action.body.as_ref() == b"arrow" => {
+                    running_queries_arrow(&queries).map_err(|e| Status::internal(e.to_string()))?
+                }
+                response => action_body(response).await?,
+            },
+            "killall" => action_body(query::kill_all_connections(state).await?).await?,
+            "killall_database" => {
+                action_body(query::killall_queries_for_database(state, action_argument(&action)?).await?).await?
+            }
+            "status" => serde_json::to_vec(&query::status(state).await?)
+                .map_err(|e| Status::internal(e.to_string()))?,
+            "reconnect" => {
+                let database = action_argument(&action)?;
+                state.reconnect_db(&database).await?;
+                serde_json::json!({ "status": "reconnected", "database": database })
+                    .to_string()
+                    .into_bytes()
+            }
+            _ => {
+                return Err(Status::unimplemented(format!(
+                    "Action '{}' not implemented",
+                    action.r#type
+                )));
+            }
+        };
+
+        let response = arrow_flight::Result { body: body.into() };
+        let stream = futures::stream::once(async { Ok(response) });
+        Ok(Response::new(Box::pin(stream)))
     }
 
-    async fn list_actions(&self, _request: Request<Empty>) -> Result<Response<Self::ListActionsStream>, Status> {
-        let actions = vec![arrow_flight::ActionType {
-            r#type: "healthcheck".to_string(),
-            description: "Health check action".to_string(),
-        }];
-        let stream = futures::stream::iter(actions.into_iter().map(Ok));
+    async fn list_actions(&self, request: Request<Empty>) -> Result<Response<Self::ListActionsStream>, Status> {
+        authorize_flight(&request)?;
+
+        let actions: Vec<ActionType> = ADMIN_ACTIONS
+            .iter()
+            .map(|(name, description)| ActionType {
+                r#type: name.to_string(),
+                description: description.to_string(),
+            })
+            .collect();
+        let sql_actions = FlightService::list_actions(&self.sql, request).await?.into_inner();
+        let stream = futures::stream::iter(actions.into_iter().map(Ok)).chain(sql_actions);
         Ok(Response::new(Box::pin(stream)))
     }
 }
@@ -158,7 +869,8 @@ impl FlightService for FlightServer {
 pub async fn serve(
     addr: SocketAddr,
     state: Arc<AppState>,
-    cancel_token: tokio_util::sync::CancellationToken,
+    auth_config: Option<AuthConfig>,
+    cancel_token: CancellationToken,
 ) -> Result<(), Box<dyn std::error::Error>> {
     tracing::info!("Starting Arrow Flight Server at {}", addr);
 
@@ -166,7 +878,10 @@ pub async fn serve(
     health_reporter.set_serving::<FlightServiceServer<FlightServer>>().await;
 
     Server::builder()
-        .add_service(FlightServiceServer::new(FlightServer::new(state)))
+        .add_service(FlightServiceServer::with_interceptor(
+            FlightServer::new(state, auth_config.clone()),
+            flight_auth_interceptor(auth_config),
+        ))
         .add_service(health_service)
         .serve_with_shutdown(addr, cancel_token.cancelled())
         .await?;
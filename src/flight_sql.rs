@@ -0,0 +1,698 @@
+#![allow(clippy::result_large_err)]
+
+use crate::{
+    constants::{FULL_VERSION, METADATA_ROW_LIMIT},
+    flight::{ingest_flight_data, query_flight_data},
+    interfaces::{Command as QueryCommand, DbState, QueryParams, SqlValue},
+    sql::schema_probe_sql,
+    state::AppState,
+};
+use arrow::{
+    array::{Array, AsArray},
+    datatypes::{DataType, Field, Schema},
+    record_batch::RecordBatch,
+};
+use arrow_flight::{
+    FlightDescriptor, FlightEndpoint, flight_descriptor::DescriptorType, FlightInfo, IpcMessage, SchemaAsIpc, Ticket,
+    decode::FlightRecordBatchStream,
+    encode::FlightDataEncoderBuilder,
+    error::FlightError,
+    flight_service_server::FlightService,
+    sql::{
+        ActionClosePreparedStatementRequest, Any, ActionCreatePreparedStatementRequest,
+        ActionCreatePreparedStatementResult, CommandGetCatalogs, CommandGetDbSchemas, CommandGetSqlInfo,
+        CommandGetTableTypes, CommandGetTables, CommandPreparedStatementQuery, CommandPreparedStatementUpdate, CommandStatementQuery,
+        CommandStatementUpdate, DoPutPreparedStatementResult, ProstMessageExt, SqlInfo, SqlSupportedTransaction,
+        TicketStatementQuery,
+        metadata::{SqlInfoData, SqlInfoDataBuilder},
+        server::{FlightSqlService, PeekableFlightDataStream},
+    },
+};
+use futures::TryStreamExt;
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+use tonic::{Request, Response, Status};
+
+pub(crate) const FLIGHT_SQL_TYPE_URL_PREFIX: &str = "type.googleapis.com/arrow.flight.protocol.sql.";
+
+pub(crate) const FLIGHT_SQL_ACTIONS: &[&str] = &[
+    "CreatePreparedStatement",
+    "ClosePreparedStatement",
+    "CreatePreparedSubstraitPlan",
+    "BeginTransaction",
+    "EndTransaction",
+    "BeginSavepoint",
+    "EndSavepoint",
+    "CancelQuery",
+];
+
+/// gRPC metadata key Flight SQL clients use to pick the database, e.g. the ADBC
+/// `adbc.flight.sql.rpc.call_header.database` option.
+const DATABASE_HEADER: &str = "database";
+
+const TABLE_TYPES: &[&str] = &["BASE TABLE", "LOCAL TEMPORARY", "VIEW"];
+
+/// Opaque handle for statements and prepared statements. Prepared statements are
+/// stateless: the handle carries the SQL and the parameter rows bound to it.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+struct StatementHandle {
+    database: String,
+    sql: String,
+    #[serde(default)]
+    parameter_rows: Vec<Vec<SqlValue>>,
+}
+
+impl StatementHandle {
+    fn encode(&self) -> Result<Vec<u8>, Status> {
+        serde_json::to_vec(self).map_err(|e| Status::internal(e.to_string()))
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, Status> {
+        serde_json::from_slice(bytes)
+            .map_err(|e| Status::invalid_argument(format!("Invalid statement handle: {}", e)))
+    }
+
+    /// The arguments of a query. It returns a single result set, so it binds at most one row.
+    fn args(&self) -> Result<Option<Vec<SqlValue>>, Status> {
+        match self.parameter_rows.as_slice() {
+            [] => Ok(None),
+            [row] => Ok(Some(row.clone())),
+            rows => Err(Status::invalid_argument(format!(
+                "Prepared queries bind one parameter row, got {}",
+                rows.len()
+            ))),
+        }
+    }
+
+    fn into_params(self) -> Result<QueryParams, Status> {
+        Ok(QueryParams {
+            args: self.args()?,
+            database: self.database,
+            query_type: Some(QueryCommand::Arrow),
+            sql: Some(self.sql),
+            ..Default::default()
+        })
+    }
+}
+
+pub struct FlightSqlServer {
+    state: Arc<AppState>,
+    sql_info: SqlInfoData,
+}
+
+impl FlightSqlServer {
+    pub fn new(state: Arc<AppState>) -> Self {
+        let mut builder = SqlInfoDataBuilder::new();
+        builder.append(SqlInfo::FlightSqlServerName, "DuckDB Server");
+        builder.append(SqlInfo::FlightSqlServerVersion, FULL_VERSION.as_str());
+        builder.append(SqlInfo::FlightSqlServerArrowVersion, "1.3");
+        builder.append(
+            SqlInfo::FlightSqlServerReadOnly,
+            state.defaults.access_mode.eq_ignore_ascii_case("readonly"),
+        );
+        builder.append(SqlInfo::FlightSqlServerSql, true);
+        builder.append(SqlInfo::FlightSqlServerSubstrait, false);
+        builder.append(SqlInfo::FlightSqlServerTransaction, SqlSupportedTransaction::None as i32);
+        builder.append(SqlInfo::FlightSqlServerCancel, false);
+        builder.append(SqlInfo::SqlIdentifierQuoteChar, "\"");
+        builder.append(SqlInfo::SqlDdlCatalog, true);
+        builder.append(SqlInfo::SqlDdlSchema, true);
+        builder.append(SqlInfo::SqlDdlTable, true);
+
+        let sql_info = builder.build().expect("valid Flight SQL info");
+
+        Self { state, sql_info }
+    }
+
+    async fn db_state(&self, database: &str) -> Result<Arc<DbState>, Status> {
+        self.state
+            .get_or_create_db_state(database, &None, &None, &None)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))
+    }
+
+    /// The result schema of a query. Other statements have none, so they are refused rather than
+    /// described with an empty schema; run them with `CommandStatementUpdate` instead.
+    async fn get_schema(&self, handle: &StatementHandle) -> Result<Arc<Schema>, Status> {
+        if schema_probe_sql(&handle.sql).is_none() {
+            return Err(Status::invalid_argument(
+                "Only queries have a result schema; execute other statements as updates",
+            ));
+        }
+
+        self.db_state(&handle.database)
+            .await?
+            .db
+            .get_schema(&handle.sql, &handle.args()?, &None, &None, &None, &None, &None, &None, &None)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))
+    }
+
+    /// The schemas of the tables and views matching `conditions` on `information_schema.columns`,
+    /// keyed by catalog, schema and table. The columns come from one listing, and their DuckDB
+    /// types are turned into Arrow types by probing every distinct type once in a single query.
+    pub(crate) async fn table_schemas(
+        &self,
+        database: &str,
+        conditions: &[&str],
+        args: Vec<SqlValue>,
+    ) -> Result<BTreeMap<(String, String, String), Schema>, Status> {
+        let mut sql = "SELECT table_catalog, table_schema, table_name, column_name, data_type, is_nullable \
+            FROM information_schema.columns"
+            .to_string();
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(" ORDER BY table_catalog, table_schema, table_name, ordinal_position");
+
+        let batches = self.metadata_batches(database, &sql, args).await?;
+
+        let mut columns = Vec::new();
+        // Distinct DuckDB types, each with the index of its column in the probe
+        let mut types: BTreeMap<String, usize> = BTreeMap::new();
+        for batch in &batches {
+            let catalogs = string_column(batch, 0)?;
+            let schemas = string_column(batch, 1)?;
+            let tables = string_column(batch, 2)?;
+            let names = string_column(batch, 3)?;
+            let data_types = string_column(batch, 4)?;
+            let nullables = string_column(batch, 5)?;
+
+            for (((((catalog, schema), table), name), data_type), nullable) in
+                catalogs.into_iter().zip(schemas).zip(tables).zip(names).zip(data_types).zip(nullables)
+            {
+                let count = types.len();
+                let index = *types.entry(data_type).or_insert(count);
+                columns.push(((catalog, schema, table), name, index, nullable == "YES"));
+            }
+        }
+
+        if types.is_empty() {
+            return Ok(BTreeMap::new());
+        }
+
+        let mut probe_columns: Vec<(&String, usize)> = types.iter().map(|(data_type, index)| (data_type, *index)).collect();
+        probe_columns.sort_by_key(|(_, index)| *index);
+
+        let probe = StatementHandle {
+            database: database.to_string(),
+            sql: format!(
+                "SELECT {}",
+                probe_columns
+                    .iter()
+                    .map(|(data_type, index)| format!("NULL::{} AS \"{}\"", data_type, index))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            parameter_rows: vec![],
+        };
+        let arrow_types = self.get_schema(&probe).await?;
+
+        let mut fields: BTreeMap<(String, String, String), Vec<Field>> = BTreeMap::new();
+        for (table, name, index, nullable) in columns {
+            let arrow_type = arrow_types.field(index).data_type().clone();
+            fields.entry(table).or_default().push(Field::new(name, arrow_type, nullable));
+        }
+
+        Ok(fields.into_iter().map(|(table, fields)| (table, Schema::new(fields))).collect())
+    }
+
+    pub(crate) async fn metadata_batches(
+        &self,
+        database: &str,
+        sql: &str,
+        args: Vec<SqlValue>,
+    ) -> Result<Vec<RecordBatch>, Status> {
+        self.db_state(database)
+            .await?
+            .db
+            .get_record_batches(
+                &sql.to_string(),
+                &Some(args),
+                &None,
+                &None,
+                &None,
+                &None,
+                METADATA_ROW_LIMIT,
+                &None,
+                &None,
+                &None,
+                &CancellationToken::new(),
+            )
+            .await
+            .map_err(|e| Status::internal(e.to_string()))
+    }
+}
+
+fn database_header<T>(request: &Request<T>) -> Result<String, Status> {
+    request
+        .metadata()
+        .get(DATABASE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.trim().is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| Status::invalid_argument(format!("Missing '{}' header", DATABASE_HEADER)))
+}
+
+fn flight_info(
+    schema: &Schema,
+    ticket: impl ProstMessageExt,
+    descriptor: FlightDescriptor,
+) -> Result<Response<FlightInfo>, Status> {
+    let endpoint = FlightEndpoint::new().with_ticket(Ticket::new(ticket.as_any().encode_to_vec()));
+    let info = FlightInfo::new()
+        .try_with_schema(schema)
+        .map_err(|e| Status::internal(e.to_string()))?
+        .with_endpoint(endpoint)
+        .with_descriptor(descriptor);
+
+    Ok(Response::new(info))
+}
+
+fn batch_response(
+    batch: Result<RecordBatch, FlightError>,
+) -> Result<Response<<FlightSqlServer as FlightService>::DoGetStream>, Status> {
+    let batch = batch?;
+    let stream = FlightDataEncoderBuilder::new()
+        .with_schema(batch.schema())
+        .build(futures::stream::once(async { Ok(batch) }))
+        .map_err(Status::from);
+
+    Ok(Response::new(Box::pin(stream)))
+}
+
+fn schema_ipc(schema: &Schema) -> Result<prost::bytes::Bytes, Status> {
+    let options = arrow::ipc::writer::IpcWriteOptions::default();
+    let IpcMessage(message) = SchemaAsIpc::new(schema, &options)
+        .try_into()
+        .map_err(|e: arrow::error::ArrowError| Status::internal(e.to_string()))?;
+
+    Ok(message)
+}
+
+/// The rows of the parameter batches a client sends with `DoPut`, as arguments.
+async fn parameter_rows(request: Request<PeekableFlightDataStream>) -> Result<Vec<Vec<SqlValue>>, Status> {
+    // Updates may come as a lone descriptor without any IPC message
+    let data = request
+        .into_inner()
+        .try_filter(|data| futures::future::ready(!data.data_header.is_empty()))
+        .map_err(|e| e.into());
+    let batches: Vec<RecordBatch> = FlightRecordBatchStream::new_from_flight_data(data).try_collect().await?;
+
+    let mut rows = Vec::new();
+    for batch in &batches {
+        for row in 0..batch.num_rows() {
+            let args = batch
+                .columns()
+                .iter()
+                .map(|column| SqlValue::from_arrow(column.as_ref(), row))
+                .collect::<anyhow::Result<Vec<_>>>()
+                .map_err(|e| Status::invalid_argument(format!("Unsupported parameter value: {}", e)))?;
+            rows.push(args);
+        }
+    }
+
+    Ok(rows)
+}
+
+fn string_column(batch: &RecordBatch, index: usize) -> Result<Vec<String>, Status> {
+    let column = batch
+        .column(index)
+        .as_string_opt::<i32>()
+        .ok_or_else(|| Status::internal(format!("Expected string column at index {}", index)))?;
+
+    Ok((0..column.len()).map(|i| column.value(i).to_string()).collect())
+}
+
+#[tonic::async_trait]
+impl FlightSqlService for FlightSqlServer {
+    type FlightService = FlightSqlServer;
+
+    async fn get_flight_info_statement(
+        &self,
+        query: CommandStatementQuery,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let handle = StatementHandle {
+            database: database_header(&request)?,
+            sql: query.query,
+            parameter_rows: vec![],
+        };
+        let schema = self.get_schema(&handle).await?;
+        let ticket = TicketStatementQuery {
+            statement_handle: handle.encode()?.into(),
+        };
+
+        flight_info(&schema, ticket, request.into_inner())
+    }
+
+    async fn get_flight_info_prepared_statement(
+        &self,
+        query: CommandPreparedStatementQuery,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let handle = StatementHandle::decode(&query.prepared_statement_handle)?;
+        let schema = self.get_schema(&handle).await?;
+
+        flight_info(&schema, query, request.into_inner())
+    }
+
+    async fn get_flight_info_catalogs(
+        &self,
+        query: CommandGetCatalogs,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let schema = query.into_builder().schema();
+        flight_info(&schema, query, request.into_inner())
+    }
+
+    async fn get_flight_info_schemas(
+        &self,
+        query: CommandGetDbSchemas,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let schema = query.clone().into_builder().schema();
+        flight_info(&schema, query, request.into_inner())
+    }
+
+    async fn get_flight_info_tables(
+        &self,
+        query: CommandGetTables,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let schema = query.clone().into_builder().schema();
+        flight_info(&schema, query, request.into_inner())
+    }
+
+    async fn get_flight_info_table_types(
+        &self,
+        query: CommandGetTableTypes,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let schema = query.into_builder().schema();
+        flight_info(&schema, query, request.into_inner())
+    }
+
+    async fn get_flight_info_sql_info(
+        &self,
+        query: CommandGetSqlInfo,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        flight_info(&self.sql_info.schema(), query, request.into_inner())
+    }
+
+    async fn do_get_statement(
+        &self,
+        ticket: TicketStatementQuery,
+        _request: Request<Ticket>,
+    ) -> Result<Response<<Self as FlightService>::DoGetStream>, Status> {
+        let params = StatementHandle::decode(&ticket.statement_handle)?.into_params()?;
+        let stream = query_flight_data(&self.state, &params).await?;
+        Ok(Response::new(stream))
+    }
+
+    async fn do_get_prepared_statement(
+        &self,
+        query: CommandPreparedStatementQuery,
+        _request: Request<Ticket>,
+    ) -> Result<Response<<Self as FlightService>::DoGetStream>, Status> {
+        let params = StatementHandle::decode(&query.prepared_statement_handle)?.into_params()?;
+        let stream = query_flight_data(&self.state, &params).await?;
+        Ok(Response::new(stream))
+    }
+
+    async fn do_get_catalogs(
+        &self,
+        query: CommandGetCatalogs,
+        request: Request<Ticket>,
+    ) -> Result<Response<<Self as FlightService>::DoGetStream>, Status> {
+        let database = database_header(&request)?;
+        let batches = self
+            .metadata_batches(
+                &database,
+                "SELECT database_name FROM duckdb_databases() WHERE NOT internal ORDER BY database_name",
+                vec![],
+            )
+            .await?;
+
+        let mut builder = query.into_builder();
+        for batch in &batches {
+            for catalog in string_column(batch, 0)? {
+                builder.append(catalog);
+            }
+        }
+
+        batch_response(builder.build())
+    }
+
+    async fn do_get_schemas(
+        &self,
+        query: CommandGetDbSchemas,
+        request: Request<Ticket>,
+    ) -> Result<Response<<Self as FlightService>::DoGetStream>, Status> {
+        let database = database_header(&request)?;
+        let batches = self
+            .metadata_batches(
+                &database,
+                "SELECT catalog_name, schema_name FROM information_schema.schemata ORDER BY catalog_name, schema_name",
+                vec![],
+            )
+            .await?;
+
+        let mut builder = query.into_builder();
+        for batch in &batches {
+            let catalogs = string_column(batch, 0)?;
+            let schemas = string_column(batch, 1)?;
+            for (catalog, schema) in catalogs.iter().zip(schemas.iter()) {
+                builder.append(catalog, schema);
+            }
+        }
+
+        batch_response(builder.build())
+    }
+
+    async fn do_get_tables(
+        &self,
+        query: CommandGetTables,
+        request: Request<Ticket>,
+    ) -> Result<Response<<Self as FlightService>::DoGetStream>, Status> {
+        let database = database_header(&request)?;
+
+        let mut conditions = Vec::new();
+        let mut args = Vec::new();
+        if let Some(catalog) = &query.catalog {
+            conditions.push("table_catalog = ?");
+            args.push(SqlValue::Text(catalog.clone()));
+        }
+        if let Some(pattern) = &query.db_schema_filter_pattern {
+            conditions.push("table_schema LIKE ?");
+            args.push(SqlValue::Text(pattern.clone()));
+        }
+        if let Some(pattern) = &query.table_name_filter_pattern {
+            conditions.push("table_name LIKE ?");
+            args.push(SqlValue::Text(pattern.clone()));
+        }
+
+        let mut sql = "SELECT table_catalog, table_schema, table_name, table_type FROM information_schema.tables".to_string();
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(" ORDER BY table_catalog, table_schema, table_name");
+
+        let batches = self.metadata_batches(&database, &sql, args.clone()).await?;
+
+        let mut table_schemas = if query.include_schema {
+            self.table_schemas(&database, &conditions, args).await?
+        }
+        else {
+            BTreeMap::new()
+        };
+
+        let table_types = query.table_types.clone();
+        let mut builder = query.into_builder();
+        for batch in &batches {
+            let catalogs = string_column(batch, 0)?;
+            let schemas = string_column(batch, 1)?;
+            let tables = string_column(batch, 2)?;
+            let types = string_column(batch, 3)?;
+
+            for (((catalog, schema), table), table_type) in catalogs.into_iter().zip(schemas).zip(tables).zip(types) {
+                if !table_types.is_empty() && !table_types.contains(&table_type) {
+                    continue;
+                }
+
+                let key = (catalog, schema, table);
+                let table_schema = table_schemas.remove(&key).unwrap_or_else(Schema::empty);
+                let (catalog, schema, table) = key;
+
+                builder
+                    .append(catalog, schema, table, table_type, &table_schema)
+                    .map_err(|e| Status::internal(e.to_string()))?;
+            }
+        }
+
+        batch_response(builder.build())
+    }
+
+    async fn do_get_table_types(
+        &self,
+        query: CommandGetTableTypes,
+        _request: Request<Ticket>,
+    ) -> Result<Response<<Self as FlightService>::DoGetStream>, Status> {
+        let mut builder = query.into_builder();
+        for table_type in TABLE_TYPES {
+            builder.append(table_type);
+        }
+
+        batch_response(builder.build())
+    }
+
+    async fn do_get_sql_info(
+        &self,
+        query: CommandGetSqlInfo,
+        _request: Request<Ticket>,
+    ) -> Result<Response<<Self as FlightService>::DoGetStream>, Status> {
+        batch_response(query.into_builder(&self.sql_info).build())
+    }
+
+    async fn do_put_statement_update(
+        &self,
+        query: CommandStatementUpdate,
+        request: Request<PeekableFlightDataStream>,
+    ) -> Result<i64, Status> {
+        let database = database_header(&request)?;
+        let rows = self
+            .db_state(&database)
+            .await?
+            .db
+            .execute(&query.query, &[], &None, &None, &CancellationToken::new())
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(rows as i64)
+    }
+
+    async fn do_put_prepared_statement_query(
+        &self,
+        query: CommandPreparedStatementQuery,
+        request: Request<PeekableFlightDataStream>,
+    ) -> Result<DoPutPreparedStatementResult, Status> {
+        // Clients bind the parameters of updates this way too, so every row is kept and
+        // queries refuse more than one when they run
+        let mut handle = StatementHandle::decode(&query.prepared_statement_handle)?;
+        handle.parameter_rows = parameter_rows(request).await?;
+
+        Ok(DoPutPreparedStatementResult {
+            prepared_statement_handle: Some(handle.encode()?.into()),
+        })
+    }
+
+    async fn do_put_prepared_statement_update(
+        &self,
+        query: CommandPreparedStatementUpdate,
+        request: Request<PeekableFlightDataStream>,
+    ) -> Result<i64, Status> {
+        let mut handle = StatementHandle::decode(&query.prepared_statement_handle)?;
+
+        // The statement runs once per parameter row, sent along or bound earlier
+        let rows = parameter_rows(request).await?;
+        if !rows.is_empty() {
+            handle.parameter_rows = rows;
+        }
+
+        let rows = self
+            .db_state(&handle.database)
+            .await?
+            .db
+            .execute(&handle.sql, &handle.parameter_rows, &None, &None, &CancellationToken::new())
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(rows as i64)
+    }
+
+    async fn do_action_create_prepared_statement(
+        &self,
+        query: ActionCreatePreparedStatementRequest,
+        request: Request<arrow_flight::Action>,
+    ) -> Result<ActionCreatePreparedStatementResult, Status> {
+        let handle = StatementHandle {
+            database: database_header(&request)?,
+            sql: query.query,
+            parameter_rows: vec![],
+        };
+
+        // Statements other than queries have no result set, so they come without a dataset schema
+        let dataset_schema = if schema_probe_sql(&handle.sql).is_some() {
+            schema_ipc(&*self.get_schema(&handle).await?)?
+        }
+        else {
+            Default::default()
+        };
+
+        // DuckDB infers parameter types from the values bound, so parameters are typed Null
+        let parameters = self
+            .db_state(&handle.database)
+            .await?
+            .db
+            .get_parameter_names(&handle.sql, &None)
+            .await
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let parameter_schema = if parameters.is_empty() {
+            Default::default()
+        }
+        else {
+            schema_ipc(&Schema::new(
+                parameters
+                    .into_iter()
+                    .map(|name| Field::new(name, DataType::Null, true))
+                    .collect::<Vec<_>>(),
+            ))?
+        };
+
+        Ok(ActionCreatePreparedStatementResult {
+            prepared_statement_handle: handle.encode()?.into(),
+            dataset_schema,
+            parameter_schema,
+        })
+    }
+
+    async fn do_action_close_prepared_statement(
+        &self,
+        _query: ActionClosePreparedStatementRequest,
+        _request: Request<arrow_flight::Action>,
+    ) -> Result<(), Status> {
+        // Prepared statements hold no server-side state
+        Ok(())
+    }
+
+    async fn do_put_fallback(
+        &self,
+        request: Request<PeekableFlightDataStream>,
+        message: Any,
+    ) -> Result<Response<<Self as FlightService>::DoPutStream>, Status> {
+        let mut stream = request.into_inner();
+        let descriptor = match stream.peek().await {
+            Some(Ok(data)) => data.flight_descriptor.clone(),
+            _ => None,
+        };
+
+        match descriptor {
+            Some(descriptor) if descriptor.r#type() == DescriptorType::Path => {
+                let result = ingest_flight_data(&self.state, &descriptor.path, stream).await?;
+                Ok(Response::new(Box::pin(futures::stream::once(async { Ok(result) }))))
+            }
+            _ => Err(Status::unimplemented(format!(
+                "do_put: The defined request is invalid: {}",
+                message.type_url
+            ))),
+        }
+    }
+
+    async fn register_sql_info(&self, _id: i32, _result: &SqlInfo) {}
+}
@@ -69,6 +69,10 @@ pub struct CliArgs {
     #[arg(long, default_value_t = 1800, env = "POOL_MAX_LIFETIME")]
     pub pool_max_lifetime: u64,
 
+    /// How long results of async queries are kept, in seconds
+    #[arg(long, default_value_t = 3600, env = "ASYNC_RESULT_RETENTION")]
+    pub async_result_retention: u64,
+
     /// Enable authentication
     #[arg(long)]
     pub service_auth_enabled: bool,
@@ -13,6 +13,7 @@ pub struct DbDefaults {
     pub pool_timeout: u64,
     pub pool_idle_timeout: u64,
     pub pool_max_lifetime: u64,
+    pub async_result_retention: u64,
 }
 
 pub struct DbState {
@@ -75,3 +75,14 @@ where
         AppError::Error(SanitizedError(err.into()))
     }
 }
+
+impl From<AppError> for tonic::Status {
+    fn from(err: AppError) -> Self {
+        match err {
+            AppError::BadRequest(error) => tonic::Status::invalid_argument(format!("Bad request: {error}")),
+            AppError::RetriesExceeded(error) => tonic::Status::unavailable(format!("Retries exceeded: {error}")),
+            AppError::Timeout => tonic::Status::deadline_exceeded("Request timed out"),
+            AppError::Error(error) => tonic::Status::internal(error.to_string()),
+        }
+    }
+}
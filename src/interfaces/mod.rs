@@ -9,4 +9,8 @@ pub use cli::{CliArgs, Cli, CliCommand};
 pub use config::{DucklakeConfig, Extension, SecretConfig, SettingConfig};
 pub use db::{DbDefaults, DbState, DbType};
 pub use error::AppError;
-pub use query::{Command, QueryInfo, QueryParams, QueryResponse, SqlValue};
+pub use query::{
+    ArrowCompression, ArrowFormat, AsyncQueryInfo, AsyncResultParams, CatalogObject, CatalogParams, Command, IngestMode,
+    JsonFormat, PrepareParams, QueryInfo, QueryParams, QueryResponse, ResultMetadata, ResultStream, SessionParams,
+    SqlValue, SubmitOptions, TimestampFormat, UploadFormat, UploadParams,
+};
@@ -1,12 +1,22 @@
+use arrow::{
+    array::{Array, AsArray},
+    compute::{CastOptions, cast_with_options},
+    datatypes::{DataType, Float64Type, Int64Type},
+};
 use axum::{
-    body::Bytes,
-    http::StatusCode,
-    response::{IntoResponse, Response},
+    body::{Body, Bytes},
+    http::{HeaderValue, StatusCode},
+    response::{IntoResponse, Json, Response},
 };
-use duckdb::types::ToSql;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use duckdb::types::{TimeUnit, ToSql, Value};
+use futures::{TryStreamExt, stream::BoxStream};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 use super::config::{DucklakeConfig, Extension, SecretConfig};
+use crate::sql::quote_literal;
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "kebab-case")]
@@ -14,8 +24,91 @@ pub enum Command {
     Arrow,
     Exec,
     Json,
+    Csv,
+    Ndjson,
+    Parquet,
+    Explain,
+    CreateBundle,
+    LoadBundle,
+}
+
+/// How Flight `do_put` writes incoming batches into the target table.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum IngestMode {
+    /// Create the table from the stream's schema, failing if it already exists.
+    Create,
+    /// Append to an existing table.
+    #[default]
+    Append,
+    /// Create the table, dropping any existing table of the same name.
+    Replace,
+}
+
+impl std::str::FromStr for IngestMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "create" => Ok(IngestMode::Create),
+            "append" => Ok(IngestMode::Append),
+            "replace" => Ok(IngestMode::Replace),
+            _ => Err(format!("Unknown ingest mode '{}', expected create, append or replace", s)),
+        }
+    }
+}
+
+/// Arrow IPC framing of `arrow` results.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArrowFormat {
+    #[default]
+    Stream,
+    File,
 }
 
+impl ArrowFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ArrowFormat::Stream => "application/vnd.apache.arrow.stream",
+            ArrowFormat::File => "application/vnd.apache.arrow.file",
+        }
+    }
+}
+
+/// Buffer compression of `arrow` results.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArrowCompression {
+    #[serde(alias = "lz4")]
+    Lz4Frame,
+    Zstd,
+}
+
+/// Shape of `json` results.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum JsonFormat {
+    /// An array of row objects.
+    #[default]
+    Rows,
+    /// An array of rows, each an array of its values in column order.
+    Arrays,
+    /// An object holding an array of values for each column.
+    Columnar,
+}
+
+/// How `json` and `ndjson` results write timestamps.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimestampFormat {
+    /// ISO 8601 with a UTC offset. Timestamps without a time zone are taken to be in UTC.
+    Iso,
+    /// Milliseconds since the Unix epoch.
+    EpochMs,
+}
+
+/// A query argument: a plain JSON number, string, boolean or `null`, or a [`TypedValue`].
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum SqlValue {
@@ -24,20 +117,266 @@ pub enum SqlValue {
     Text(String),
     Bool(bool),
     Null,
+    Typed(TypedValue),
+}
+
+/// An argument of an explicit DuckDB type, e.g. `{"type": "date", "value": "2024-01-31"}`.
+/// Values JSON cannot carry exactly are strings in DuckDB's text format. `List` and `Struct`
+/// values are built from typed children, e.g. a `List` of `Integer`s for `a IN ?`.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum TypedValue {
+    Boolean(bool),
+    Tinyint(i8),
+    Smallint(i16),
+    Integer(i32),
+    Bigint(i64),
+    Hugeint(String),
+    Utinyint(u8),
+    Usmallint(u16),
+    Uinteger(u32),
+    Ubigint(u64),
+    Float(f32),
+    Double(f64),
+    Decimal(String),
+    Varchar(String),
+    /// Base64 encoded bytes.
+    Blob(String),
+    Date(String),
+    Time(String),
+    Timestamp(String),
+    Timestamptz(String),
+    Interval(String),
+    Uuid(String),
+    List(Vec<SqlValue>),
+    Struct(BTreeMap<String, SqlValue>),
 }
 
 impl SqlValue {
-    pub fn as_tosql(&self) -> Box<dyn ToSql> {
-        match self {
+    pub fn as_tosql(&self) -> anyhow::Result<Box<dyn ToSql>> {
+        let value: Box<dyn ToSql> = match self {
             SqlValue::Int(v) => Box::new(*v),
             SqlValue::Float(v) => Box::new(*v),
             SqlValue::Text(v) => Box::new(v.clone()),
             SqlValue::Bool(v) => Box::new(*v),
             SqlValue::Null => Box::new(None::<i32>),
+            SqlValue::Typed(v) => v.as_tosql()?,
+        };
+
+        Ok(value)
+    }
+
+    /// Whether DuckDB's client API can bind the value. The others are written into the
+    /// statement as [`SqlValue::sql_literal`]s.
+    pub fn is_bindable(&self) -> bool {
+        !matches!(
+            self,
+            SqlValue::Typed(
+                TypedValue::Decimal(_)
+                    | TypedValue::Date(_)
+                    | TypedValue::Time(_)
+                    | TypedValue::Timestamptz(_)
+                    | TypedValue::Interval(_)
+                    | TypedValue::Uuid(_)
+                    | TypedValue::List(_)
+                    | TypedValue::Struct(_)
+            )
+        )
+    }
+
+    /// The value as a SQL expression of its DuckDB type, e.g. `CAST('2024-01-31' AS DATE)`.
+    pub fn sql_literal(&self) -> anyhow::Result<String> {
+        let literal = match self {
+            SqlValue::Int(v) => format!("CAST({} AS BIGINT)", v),
+            SqlValue::Float(v) => float_literal(*v, "DOUBLE"),
+            SqlValue::Text(v) => format!("CAST({} AS VARCHAR)", quote_literal(v)),
+            SqlValue::Bool(v) => v.to_string().to_uppercase(),
+            SqlValue::Null => "NULL".to_string(),
+            SqlValue::Typed(v) => v.sql_literal()?,
+        };
+
+        Ok(literal)
+    }
+
+    pub fn from_arrow(array: &dyn Array, row: usize) -> anyhow::Result<Self> {
+        if array.is_null(row) {
+            return Ok(SqlValue::Null);
+        }
+
+        let value = array.slice(row, 1);
+        let cast = |data_type: &DataType| {
+            cast_with_options(&value, data_type, &CastOptions { safe: false, ..Default::default() })
+        };
+        let result = match array.data_type() {
+            DataType::Null => SqlValue::Null,
+            DataType::Boolean => SqlValue::Bool(value.as_boolean().value(0)),
+            data_type if data_type.is_integer() => {
+                SqlValue::Int(cast(&DataType::Int64)?.as_primitive::<Int64Type>().value(0))
+            }
+            data_type if data_type.is_floating() || data_type.is_numeric() => {
+                SqlValue::Float(cast(&DataType::Float64)?.as_primitive::<Float64Type>().value(0))
+            }
+            _ => SqlValue::Text(cast(&DataType::Utf8)?.as_string::<i32>().value(0).to_string()),
+        };
+
+        Ok(result)
+    }
+}
+
+impl TypedValue {
+    fn as_tosql(&self) -> anyhow::Result<Box<dyn ToSql>> {
+        let value: Box<dyn ToSql> = match self {
+            TypedValue::Boolean(v) => Box::new(*v),
+            TypedValue::Tinyint(v) => Box::new(*v),
+            TypedValue::Smallint(v) => Box::new(*v),
+            TypedValue::Integer(v) => Box::new(*v),
+            TypedValue::Bigint(v) => Box::new(*v),
+            TypedValue::Hugeint(v) => {
+                Box::new(v.parse::<i128>().map_err(|_| anyhow::anyhow!("Invalid hugeint: {}", v))?)
+            }
+            TypedValue::Utinyint(v) => Box::new(*v),
+            TypedValue::Usmallint(v) => Box::new(*v),
+            TypedValue::Uinteger(v) => Box::new(*v),
+            TypedValue::Ubigint(v) => Box::new(*v),
+            TypedValue::Float(v) => Box::new(*v),
+            TypedValue::Double(v) => Box::new(*v),
+            TypedValue::Varchar(v) => Box::new(v.clone()),
+            TypedValue::Blob(v) => {
+                Box::new(STANDARD.decode(v).map_err(|e| anyhow::anyhow!("Invalid base64 blob: {}", e))?)
+            }
+            TypedValue::Timestamp(v) => Box::new(Value::Timestamp(TimeUnit::Microsecond, timestamp_micros(v)?)),
+            // duckdb-rs cannot bind these, see `SqlValue::is_bindable`
+            TypedValue::Decimal(_)
+            | TypedValue::Date(_)
+            | TypedValue::Time(_)
+            | TypedValue::Timestamptz(_)
+            | TypedValue::Interval(_)
+            | TypedValue::Uuid(_)
+            | TypedValue::List(_)
+            | TypedValue::Struct(_) => anyhow::bail!("{} arguments are written into the statement", self.type_name()),
+        };
+
+        Ok(value)
+    }
+
+    fn sql_literal(&self) -> anyhow::Result<String> {
+        let cast = |value: &str| format!("CAST({} AS {})", quote_literal(value), self.type_name());
+        let literal = match self {
+            TypedValue::Boolean(v) => v.to_string().to_uppercase(),
+            TypedValue::Tinyint(v) => format!("CAST({} AS TINYINT)", v),
+            TypedValue::Smallint(v) => format!("CAST({} AS SMALLINT)", v),
+            TypedValue::Integer(v) => format!("CAST({} AS INTEGER)", v),
+            TypedValue::Bigint(v) => format!("CAST({} AS BIGINT)", v),
+            TypedValue::Hugeint(v) => {
+                let v = v.parse::<i128>().map_err(|_| anyhow::anyhow!("Invalid hugeint: {}", v))?;
+                format!("CAST({} AS HUGEINT)", v)
+            }
+            TypedValue::Utinyint(v) => format!("CAST({} AS UTINYINT)", v),
+            TypedValue::Usmallint(v) => format!("CAST({} AS USMALLINT)", v),
+            TypedValue::Uinteger(v) => format!("CAST({} AS UINTEGER)", v),
+            TypedValue::Ubigint(v) => format!("CAST({} AS UBIGINT)", v),
+            TypedValue::Float(v) => float_literal(f64::from(*v), "FLOAT"),
+            TypedValue::Double(v) => float_literal(*v, "DOUBLE"),
+            TypedValue::Decimal(v) => {
+                let (precision, scale) = decimal_precision(v)?;
+                format!("CAST({} AS DECIMAL({}, {}))", quote_literal(v), precision, scale)
+            }
+            TypedValue::Varchar(v) => cast(v),
+            TypedValue::Blob(v) => {
+                STANDARD.decode(v).map_err(|e| anyhow::anyhow!("Invalid base64 blob: {}", e))?;
+                format!("from_base64({})", quote_literal(v))
+            }
+            TypedValue::Timestamp(v) => {
+                timestamp_micros(v)?;
+                cast(v)
+            }
+            TypedValue::Date(v)
+            | TypedValue::Time(v)
+            | TypedValue::Timestamptz(v)
+            | TypedValue::Interval(v)
+            | TypedValue::Uuid(v) => cast(v),
+            TypedValue::List(values) => {
+                let values = values.iter().map(SqlValue::sql_literal).collect::<anyhow::Result<Vec<_>>>()?;
+                format!("[{}]", values.join(", "))
+            }
+            TypedValue::Struct(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, value)| Ok(format!("{}: {}", quote_literal(name), value.sql_literal()?)))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                format!("{{{}}}", fields.join(", "))
+            }
+        };
+
+        Ok(literal)
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            TypedValue::Boolean(_) => "BOOLEAN",
+            TypedValue::Tinyint(_) => "TINYINT",
+            TypedValue::Smallint(_) => "SMALLINT",
+            TypedValue::Integer(_) => "INTEGER",
+            TypedValue::Bigint(_) => "BIGINT",
+            TypedValue::Hugeint(_) => "HUGEINT",
+            TypedValue::Utinyint(_) => "UTINYINT",
+            TypedValue::Usmallint(_) => "USMALLINT",
+            TypedValue::Uinteger(_) => "UINTEGER",
+            TypedValue::Ubigint(_) => "UBIGINT",
+            TypedValue::Float(_) => "FLOAT",
+            TypedValue::Double(_) => "DOUBLE",
+            TypedValue::Decimal(_) => "DECIMAL",
+            TypedValue::Varchar(_) => "VARCHAR",
+            TypedValue::Blob(_) => "BLOB",
+            TypedValue::Date(_) => "DATE",
+            TypedValue::Time(_) => "TIME",
+            TypedValue::Timestamp(_) => "TIMESTAMP",
+            TypedValue::Timestamptz(_) => "TIMESTAMPTZ",
+            TypedValue::Interval(_) => "INTERVAL",
+            TypedValue::Uuid(_) => "UUID",
+            TypedValue::List(_) => "LIST",
+            TypedValue::Struct(_) => "STRUCT",
         }
     }
 }
 
+/// Non-finite floats have no numeric literal, but DuckDB casts their names.
+fn float_literal(value: f64, type_name: &str) -> String {
+    if value.is_finite() {
+        format!("CAST({:?} AS {})", value, type_name)
+    }
+    else {
+        format!("CAST('{}' AS {})", value, type_name)
+    }
+}
+
+/// Precision and scale of a decimal in plain notation, e.g. `(5, 2)` for `-123.45`.
+fn decimal_precision(value: &str) -> anyhow::Result<(usize, usize)> {
+    let invalid = || anyhow::anyhow!("Invalid decimal: {}", value);
+    let digits = value.strip_prefix(['-', '+']).unwrap_or(value);
+    let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+
+    if integer.is_empty() || !integer.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+
+    let precision = integer.len() + fraction.len();
+    if precision > 38 {
+        return Err(invalid());
+    }
+
+    Ok((precision, fraction.len()))
+}
+
+/// Microseconds since the epoch of a timestamp in DuckDB's text format, e.g. `2024-01-31 12:00:00`.
+fn timestamp_micros(value: &str) -> anyhow::Result<i64> {
+    let timestamp = NaiveDateTime::parse_from_str(&value.replacen('T', " ", 1), "%Y-%m-%d %H:%M:%S%.f")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|date| date.and_time(NaiveTime::MIN)))
+        .map_err(|_| anyhow::anyhow!("Invalid timestamp: {}", value))?;
+
+    Ok(timestamp.and_utc().timestamp_micros())
+}
+
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 pub struct QueryParams {
     pub database: String,
@@ -49,18 +388,160 @@ pub struct QueryParams {
     pub prepare_sql: Option<String>,
     pub default_schema: Option<String>,
     pub args: Option<Vec<SqlValue>>,
+    /// Values of the `$name` placeholders, by name.
+    pub named_args: Option<BTreeMap<String, SqlValue>>,
     pub name: Option<String>,
+    pub queries: Option<Vec<String>>,
     pub limit: Option<usize>,
+    /// Runs an `explain` query and reports the actual cardinalities and timings.
+    pub analyze: Option<bool>,
+    /// Rows per page. Setting it pages through the result with a cursor.
+    pub page_size: Option<usize>,
+    pub cursor: Option<String>,
+    pub arrow_format: Option<ArrowFormat>,
+    pub compression: Option<ArrowCompression>,
+    /// Wraps a `json` result in an object with its column types, row count, whether the row
+    /// limit cut it off and how long it took. `arrow` results report the same in headers.
+    pub envelope: Option<bool>,
+    pub json_format: Option<JsonFormat>,
+    /// Writes 64-bit integers and decimals into `json` and `ndjson` results as strings, which
+    /// JavaScript clients read without losing precision.
+    pub big_numbers_as_strings: Option<bool>,
+    pub timestamp_format: Option<TimestampFormat>,
+    /// DuckDB `TimeZone` for this query only, e.g. `Europe/Berlin`. `json` and `ndjson` results
+    /// write `TIMESTAMPTZ` values in it.
+    pub timezone: Option<String>,
     pub query_id: Option<String>,
+    /// Runs the query on the connection of a session opened with `POST /sessions`.
+    pub session_id: Option<String>,
     pub create: Option<bool>,
     pub extensions: Option<Vec<Extension>>,
     pub ducklakes: Option<Vec<DucklakeConfig>>,
     pub secrets: Option<Vec<SecretConfig>>,
+    /// The registered statement being run, set by `execute_prepared`.
+    #[serde(skip)]
+    pub prepared: Option<String>,
+}
+
+/// Body of `POST /prepared`.
+#[derive(Deserialize, Debug)]
+pub struct PrepareParams {
+    pub database: String,
+    pub name: String,
+    pub sql: String,
+    pub default_schema: Option<String>,
+}
+
+/// Body of `POST /sessions`.
+#[derive(Deserialize, Debug)]
+pub struct SessionParams {
+    pub database: String,
+    /// Seconds without a query after which the session is closed.
+    pub idle_timeout: Option<u64>,
+    pub create: Option<bool>,
+}
+
+/// Query string options of `POST /query`.
+#[derive(Deserialize, Debug, Default)]
+pub struct SubmitOptions {
+    /// Run the query in the background and answer with its ID right away.
+    #[serde(rename = "async", default)]
+    pub run_async: bool,
+}
+
+/// Query string of `GET /query/{id}/result`. The result is encoded as the query's own
+/// `type` unless another one is given.
+#[derive(Deserialize, Debug, Default)]
+pub struct AsyncResultParams {
+    #[serde(rename = "type")]
+    pub query_type: Option<Command>,
+    pub name: Option<String>,
+    pub arrow_format: Option<ArrowFormat>,
+    pub compression: Option<ArrowCompression>,
+}
+
+/// What `GET /databases/{database}/catalog/{object}` lists.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum CatalogObject {
+    Schemas,
+    Tables,
+    Columns,
+    Constraints,
 }
 
+/// Query string of the catalog endpoints. `catalog`, `schema` and `table` narrow the listing
+/// down; the result is `json` unless `type` asks for `arrow`.
+#[derive(Deserialize, Debug, Default)]
+pub struct CatalogParams {
+    #[serde(rename = "type")]
+    pub query_type: Option<Command>,
+    pub catalog: Option<String>,
+    pub schema: Option<String>,
+    pub table: Option<String>,
+    pub limit: Option<usize>,
+    pub arrow_format: Option<ArrowFormat>,
+    pub compression: Option<ArrowCompression>,
+    pub persist: Option<bool>,
+    pub invalidate: Option<bool>,
+}
+
+/// File formats accepted by the table upload endpoint.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum UploadFormat {
+    Csv,
+    Parquet,
+    /// A JSON array of objects or newline-delimited JSON.
+    Json,
+    /// Arrow IPC, in the stream or the file format.
+    Arrow,
+}
+
+impl UploadFormat {
+    /// The format a content type or a file extension stands for.
+    pub fn detect(name: &str) -> Option<Self> {
+        let name = name.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        let extension = name.rsplit('.').next().unwrap_or_default();
+
+        match (name.as_str(), extension) {
+            ("text/csv", _) | (_, "csv" | "tsv") => Some(UploadFormat::Csv),
+            ("application/vnd.apache.parquet" | "application/x-parquet", _) | (_, "parquet") => {
+                Some(UploadFormat::Parquet)
+            }
+            ("application/json" | "application/x-ndjson", _) | (_, "json" | "ndjson" | "jsonl") => {
+                Some(UploadFormat::Json)
+            }
+            ("application/vnd.apache.arrow.stream" | "application/vnd.apache.arrow.file", _)
+            | (_, "arrow" | "arrows" | "feather" | "ipc") => Some(UploadFormat::Arrow),
+            _ => None,
+        }
+    }
+}
+
+/// Query string of the table upload endpoint. Without a `format`, it is told by the content type
+/// or the file name of the upload.
+#[derive(Deserialize, Debug, Default)]
+pub struct UploadParams {
+    pub format: Option<UploadFormat>,
+    pub mode: Option<IngestMode>,
+    pub schema: Option<String>,
+}
+
+/// Response body chunks produced while the query is still running.
+pub type ResultStream = BoxStream<'static, anyhow::Result<Bytes>>;
+
 pub enum QueryResponse {
-    Arrow(Vec<u8>),
+    Arrow(Vec<u8>, ArrowFormat),
     Json(String),
+    ArrowStream(ResultStream, ArrowFormat),
+    JsonStream(ResultStream),
+    /// A result sent as a file attachment, e.g. CSV or Parquet.
+    Download {
+        body: ResultStream,
+        content_type: &'static str,
+        filename: String,
+    },
     Empty,
     QueryCancelled {
         query_id: String,
@@ -68,10 +549,33 @@ pub enum QueryResponse {
     RunningQueries {
         queries: Vec<QueryInfo>,
     },
+    AsyncQuery(AsyncQueryInfo),
+    /// One page of a result. `cursor` fetches the next page while `has_more` is set.
+    Page {
+        cursor: Option<String>,
+        has_more: bool,
+        result: Box<QueryResponse>,
+    },
     QueryWithId {
         query_id: String,
         result: Box<QueryResponse>,
     },
+    /// A result sent with its [`ResultMetadata`] in headers.
+    WithMetadata {
+        metadata: ResultMetadata,
+        result: Box<QueryResponse>,
+    },
+}
+
+/// What the envelope of a result tells about it.
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct ResultMetadata {
+    pub row_count: usize,
+    /// The row limit cut the result off.
+    pub truncated: bool,
+    pub elapsed_ms: u64,
+    /// The result came out of the result cache.
+    pub cached: bool,
 }
 
 #[derive(Serialize, Clone)]
@@ -82,42 +586,122 @@ pub struct QueryInfo {
     pub started_at: String,
 }
 
+#[derive(Serialize, Clone)]
+pub struct AsyncQueryInfo {
+    pub query_id: String,
+    pub database: String,
+    pub sql: String,
+    pub status: &'static str,
+    pub submitted_at: String,
+    pub elapsed_ms: u64,
+    /// Rows produced so far.
+    pub rows: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+fn query_cancelled_json(query_id: &str) -> String {
+    serde_json::json!({
+        "status": "cancelled",
+        "query_id": query_id
+    })
+    .to_string()
+}
+
+fn running_queries_json(queries: &[QueryInfo]) -> String {
+    serde_json::json!({
+        "status": "running_queries",
+        "queries": queries
+    })
+    .to_string()
+}
+
+impl QueryResponse {
+    /// The response body without HTTP framing, for the Flight server.
+    pub async fn into_body(self) -> anyhow::Result<Vec<u8>> {
+        let body = match self {
+            QueryResponse::Arrow(bytes, _) => bytes,
+            QueryResponse::Json(value) => value.into_bytes(),
+            QueryResponse::ArrowStream(stream, _)
+            | QueryResponse::JsonStream(stream)
+            | QueryResponse::Download { body: stream, .. } => {
+                stream
+                    .try_fold(Vec::new(), |mut body, chunk| async move {
+                        body.extend_from_slice(&chunk);
+                        Ok(body)
+                    })
+                    .await?
+            }
+            QueryResponse::Empty => Vec::new(),
+            QueryResponse::QueryCancelled { query_id } => query_cancelled_json(&query_id).into_bytes(),
+            QueryResponse::RunningQueries { queries } => running_queries_json(&queries).into_bytes(),
+            QueryResponse::AsyncQuery(info) => serde_json::to_vec(&info)?,
+            QueryResponse::Page { result, .. }
+            | QueryResponse::QueryWithId { result, .. }
+            | QueryResponse::WithMetadata { result, .. } => {
+                Box::pin(result.into_body()).await?
+            }
+        };
+
+        Ok(body)
+    }
+}
+
 impl IntoResponse for QueryResponse {
     fn into_response(self) -> Response {
         match self {
-            QueryResponse::Arrow(bytes) => (
+            QueryResponse::Arrow(bytes, format) => (
                 StatusCode::OK,
-                [("Content-Type", "application/vnd.apache.arrow.stream")],
+                [("Content-Type", format.content_type())],
                 Bytes::from(bytes),
             )
                 .into_response(),
             QueryResponse::Json(value) => {
                 (StatusCode::OK, [("Content-Type", "application/json")], value).into_response()
             }
+            QueryResponse::ArrowStream(stream, format) => (
+                StatusCode::OK,
+                [("Content-Type", format.content_type())],
+                Body::from_stream(stream),
+            )
+                .into_response(),
+            QueryResponse::JsonStream(stream) => (
+                StatusCode::OK,
+                [("Content-Type", "application/json")],
+                Body::from_stream(stream),
+            )
+                .into_response(),
+            QueryResponse::Download { body, content_type, filename } => (
+                StatusCode::OK,
+                [
+                    ("Content-Type", content_type.to_string()),
+                    ("Content-Disposition", format!("attachment; filename=\"{}\"", filename)),
+                ],
+                Body::from_stream(body),
+            )
+                .into_response(),
             QueryResponse::Empty => StatusCode::OK.into_response(),
-            QueryResponse::QueryCancelled { query_id } => {
-                let response = serde_json::json!({
-                    "status": "cancelled",
-                    "query_id": query_id
-                });
-                (
-                    StatusCode::OK,
-                    [("Content-Type", "application/json")],
-                    response.to_string(),
-                )
-                    .into_response()
-            }
-            QueryResponse::RunningQueries { queries } => {
-                let response = serde_json::json!({
-                    "status": "running_queries",
-                    "queries": queries
-                });
-                (
-                    StatusCode::OK,
-                    [("Content-Type", "application/json")],
-                    response.to_string(),
-                )
-                    .into_response()
+            QueryResponse::QueryCancelled { query_id } => (
+                StatusCode::OK,
+                [("Content-Type", "application/json")],
+                query_cancelled_json(&query_id),
+            )
+                .into_response(),
+            QueryResponse::RunningQueries { queries } => (
+                StatusCode::OK,
+                [("Content-Type", "application/json")],
+                running_queries_json(&queries),
+            )
+                .into_response(),
+            QueryResponse::AsyncQuery(info) => Json(info).into_response(),
+            QueryResponse::Page { cursor, has_more, result } => {
+                let mut response = (*result).into_response();
+                let headers = response.headers_mut();
+                if let Some(header_value) = cursor.and_then(|cursor| cursor.parse().ok()) {
+                    headers.insert("X-Cursor", header_value);
+                }
+                headers.insert("X-Has-More", HeaderValue::from_static(if has_more { "true" } else { "false" }));
+                response
             }
             QueryResponse::QueryWithId { query_id, result } => {
                 let mut response = (*result).into_response();
@@ -126,6 +710,105 @@ impl IntoResponse for QueryResponse {
                 }
                 response
             }
+            QueryResponse::WithMetadata { metadata, result } => {
+                let mut response = (*result).into_response();
+                let headers = response.headers_mut();
+                headers.insert("X-Row-Count", HeaderValue::from(metadata.row_count));
+                headers.insert("X-Truncated", HeaderValue::from_static(if metadata.truncated { "true" } else { "false" }));
+                headers.insert("X-Elapsed-Ms", HeaderValue::from(metadata.elapsed_ms));
+                headers.insert("X-Cached", HeaderValue::from_static(if metadata.cached { "true" } else { "false" }));
+                response
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use duckdb::types::ToSqlOutput;
+
+    fn bound(arg: serde_json::Value) -> anyhow::Result<Value> {
+        let arg: SqlValue = serde_json::from_value(arg)?;
+        match arg.as_tosql()?.to_sql()? {
+            ToSqlOutput::Owned(value) => Ok(value),
+            ToSqlOutput::Borrowed(value) => Ok(value.into()),
+            _ => anyhow::bail!("Unexpected output"),
         }
     }
+
+    #[test]
+    fn test_plain_args() {
+        assert_eq!(bound(serde_json::json!(42)).unwrap(), Value::BigInt(42));
+        assert_eq!(bound(serde_json::json!("a")).unwrap(), Value::Text("a".to_string()));
+        assert_eq!(bound(serde_json::json!(null)).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn test_typed_args() {
+        let arg = serde_json::json!({"type": "smallint", "value": 7});
+        assert_eq!(bound(arg).unwrap(), Value::SmallInt(7));
+
+        let arg = serde_json::json!({"type": "hugeint", "value": "170141183460469231731687303715884105727"});
+        assert_eq!(bound(arg).unwrap(), Value::HugeInt(i128::MAX));
+
+        let arg = serde_json::json!({"type": "blob", "value": "AAEC"});
+        assert_eq!(bound(arg).unwrap(), Value::Blob(vec![0, 1, 2]));
+    }
+
+    fn literal(arg: serde_json::Value) -> anyhow::Result<String> {
+        serde_json::from_value::<SqlValue>(arg)?.sql_literal()
+    }
+
+    #[test]
+    fn test_typed_literals() {
+        let arg = serde_json::json!({"type": "date", "value": "2024-01-31"});
+        assert_eq!(literal(arg).unwrap(), "CAST('2024-01-31' AS DATE)");
+
+        let arg = serde_json::json!({"type": "decimal", "value": "-123.45"});
+        assert_eq!(literal(arg).unwrap(), "CAST('-123.45' AS DECIMAL(5, 2))");
+
+        let arg = serde_json::json!({"type": "list", "value": [1, {"type": "integer", "value": 2}, null]});
+        assert_eq!(literal(arg).unwrap(), "[CAST(1 AS BIGINT), CAST(2 AS INTEGER), NULL]");
+
+        let arg = serde_json::json!({"type": "struct", "value": {"it's": "a", "b": 1.5}});
+        assert_eq!(literal(arg).unwrap(), "{'b': CAST(1.5 AS DOUBLE), 'it''s': CAST('a' AS VARCHAR)}");
+    }
+
+    #[test]
+    fn test_typed_timestamp() {
+        let arg = serde_json::json!({"type": "timestamp", "value": "2024-01-31T12:00:00.5"});
+        assert_eq!(bound(arg).unwrap(), Value::Timestamp(TimeUnit::Microsecond, 1_706_702_400_500_000));
+
+        let arg = serde_json::json!({"type": "timestamp", "value": "2024-01-31"});
+        assert_eq!(bound(arg).unwrap(), Value::Timestamp(TimeUnit::Microsecond, 1_706_659_200_000_000));
+
+        assert!(bound(serde_json::json!({"type": "timestamp", "value": "yesterday"})).is_err());
+    }
+
+    #[test]
+    fn test_upload_format_from_file_name() {
+        assert_eq!(UploadFormat::detect("sales.csv"), Some(UploadFormat::Csv));
+        assert_eq!(UploadFormat::detect("SALES.TSV"), Some(UploadFormat::Csv));
+        assert_eq!(UploadFormat::detect("part-0.parquet"), Some(UploadFormat::Parquet));
+        assert_eq!(UploadFormat::detect("events.jsonl"), Some(UploadFormat::Json));
+        assert_eq!(UploadFormat::detect("batches.arrows"), Some(UploadFormat::Arrow));
+        assert_eq!(UploadFormat::detect("notes.txt"), None);
+    }
+
+    #[test]
+    fn test_upload_format_from_content_type() {
+        assert_eq!(UploadFormat::detect("text/csv; charset=utf-8"), Some(UploadFormat::Csv));
+        assert_eq!(UploadFormat::detect("application/x-ndjson"), Some(UploadFormat::Json));
+        assert_eq!(UploadFormat::detect("application/vnd.apache.arrow.stream"), Some(UploadFormat::Arrow));
+        assert_eq!(UploadFormat::detect("application/octet-stream"), None);
+    }
+
+    #[test]
+    fn test_invalid_args() {
+        assert!(bound(serde_json::json!({"type": "hugeint", "value": "1.5"})).is_err());
+        assert!(bound(serde_json::json!({"type": "blob", "value": "not base64!"})).is_err());
+        assert!(literal(serde_json::json!({"type": "decimal", "value": "1e5"})).is_err());
+        assert!(literal(serde_json::json!({"type": "list", "value": [{"type": "blob", "value": "not base64!"}]})).is_err());
+    }
 }
@@ -0,0 +1,422 @@
+use anyhow::Result;
+use arrow::{
+    array::{Array, AsArray, StructArray, timezone::Tz},
+    datatypes::{
+        DataType, Field, FieldRef, SchemaRef, TimeUnit, TimestampMicrosecondType, TimestampMillisecondType,
+        TimestampNanosecondType, TimestampSecondType,
+    },
+    error::ArrowError,
+    record_batch::RecordBatch,
+    temporal_conversions::as_datetime_with_timezone,
+    util::display::{ArrayFormatter, FormatOptions},
+};
+use arrow_json::{Encoder, EncoderFactory, EncoderOptions, LineDelimitedWriter, WriterBuilder, writer::NullableEncoder};
+use chrono::SecondsFormat;
+use std::io::Write;
+use std::sync::Arc;
+
+use crate::interfaces::{JsonFormat, QueryParams, TimestampFormat};
+
+/// Timestamps without a time zone, written as ISO 8601 in UTC.
+const UTC_TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.fZ";
+
+/// Overrides how arrow-json writes big numbers and timestamps, at any depth of the result.
+#[derive(Debug)]
+struct ValueEncoders {
+    big_numbers_as_strings: bool,
+    timestamp_format: Option<TimestampFormat>,
+    timezone: Option<Tz>,
+}
+
+/// Writes a value in Arrow's display format as a JSON string.
+struct QuotedEncoder<'a>(ArrayFormatter<'a>);
+
+impl Encoder for QuotedEncoder<'_> {
+    fn encode(&mut self, idx: usize, out: &mut Vec<u8>) {
+        out.push(b'"');
+        let _ = write!(out, "{}", self.0.value(idx));
+        out.push(b'"');
+    }
+}
+
+/// Writes timestamps as milliseconds since the epoch.
+struct EpochMillisEncoder<'a> {
+    values: &'a [i64],
+    unit: TimeUnit,
+}
+
+impl Encoder for EpochMillisEncoder<'_> {
+    fn encode(&mut self, idx: usize, out: &mut Vec<u8>) {
+        let value = self.values[idx];
+        let millis = match self.unit {
+            TimeUnit::Second => value.saturating_mul(1000),
+            TimeUnit::Millisecond => value,
+            TimeUnit::Microsecond => value.div_euclid(1_000),
+            TimeUnit::Nanosecond => value.div_euclid(1_000_000),
+        };
+        let _ = write!(out, "{}", millis);
+    }
+}
+
+/// Writes timestamps with a time zone as ISO 8601 in the query's `timezone`.
+struct ZonedEncoder<'a> {
+    values: &'a [i64],
+    unit: TimeUnit,
+    timezone: Tz,
+}
+
+impl Encoder for ZonedEncoder<'_> {
+    fn encode(&mut self, idx: usize, out: &mut Vec<u8>) {
+        let value = self.values[idx];
+        let datetime = match self.unit {
+            TimeUnit::Second => as_datetime_with_timezone::<TimestampSecondType>(value, self.timezone),
+            TimeUnit::Millisecond => as_datetime_with_timezone::<TimestampMillisecondType>(value, self.timezone),
+            TimeUnit::Microsecond => as_datetime_with_timezone::<TimestampMicrosecondType>(value, self.timezone),
+            TimeUnit::Nanosecond => as_datetime_with_timezone::<TimestampNanosecondType>(value, self.timezone),
+        };
+        match datetime {
+            Some(datetime) => {
+                let _ = write!(out, "\"{}\"", datetime.to_rfc3339_opts(SecondsFormat::AutoSi, true));
+            }
+            None => out.extend_from_slice(b"null"),
+        }
+    }
+}
+
+fn timestamp_values(array: &dyn Array, unit: TimeUnit) -> &[i64] {
+    match unit {
+        TimeUnit::Second => array.as_primitive::<TimestampSecondType>().values(),
+        TimeUnit::Millisecond => array.as_primitive::<TimestampMillisecondType>().values(),
+        TimeUnit::Microsecond => array.as_primitive::<TimestampMicrosecondType>().values(),
+        TimeUnit::Nanosecond => array.as_primitive::<TimestampNanosecondType>().values(),
+    }
+}
+
+impl EncoderFactory for ValueEncoders {
+    fn make_default_encoder<'a>(
+        &self,
+        _field: &'a FieldRef,
+        array: &'a dyn Array,
+        _options: &'a EncoderOptions,
+    ) -> Result<Option<NullableEncoder<'a>>, ArrowError> {
+        let encoder: Box<dyn Encoder + 'a> = match (array.data_type(), self.timestamp_format, self.timezone) {
+            (
+                DataType::Int64
+                | DataType::UInt64
+                | DataType::Decimal32(_, _)
+                | DataType::Decimal64(_, _)
+                | DataType::Decimal128(_, _)
+                | DataType::Decimal256(_, _),
+                _,
+                _,
+            ) if self.big_numbers_as_strings => {
+                Box::new(QuotedEncoder(ArrayFormatter::try_new(array, &FormatOptions::default())?))
+            }
+            (DataType::Timestamp(unit, Some(_)), None | Some(TimestampFormat::Iso), Some(timezone)) => {
+                Box::new(ZonedEncoder { values: timestamp_values(array, *unit), unit: *unit, timezone })
+            }
+            (DataType::Timestamp(_, timezone), Some(TimestampFormat::Iso), _) => {
+                let options = match timezone {
+                    Some(_) => FormatOptions::default(),
+                    None => FormatOptions::default().with_timestamp_format(Some(UTC_TIMESTAMP_FORMAT)),
+                };
+                Box::new(QuotedEncoder(ArrayFormatter::try_new(array, &options)?))
+            }
+            (DataType::Timestamp(unit, _), Some(TimestampFormat::EpochMs), _) => {
+                Box::new(EpochMillisEncoder { values: timestamp_values(array, *unit), unit: *unit })
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(NullableEncoder::new(encoder, array.nulls().cloned())))
+    }
+}
+
+/// The encoders for the value settings of a query, if it changes any.
+fn value_encoders(params: &QueryParams) -> Result<Option<Arc<ValueEncoders>>> {
+    let encoders = ValueEncoders {
+        big_numbers_as_strings: params.big_numbers_as_strings.unwrap_or(false),
+        timestamp_format: params.timestamp_format,
+        timezone: params.timezone.as_deref().map(str::parse).transpose()?,
+    };
+
+    let changed = encoders.big_numbers_as_strings || encoders.timestamp_format.is_some() || encoders.timezone.is_some();
+    Ok(changed.then(|| Arc::new(encoders)))
+}
+
+/// Writes an `ndjson` result with the value settings of the query.
+pub(crate) fn ndjson_writer(params: &QueryParams) -> Result<LineDelimitedWriter<Vec<u8>>> {
+    let builder = match value_encoders(params)? {
+        Some(encoders) => WriterBuilder::new().with_encoder_factory(encoders),
+        None => WriterBuilder::new(),
+    };
+    Ok(builder.build(Vec::new()))
+}
+
+/// Writes record batches as a JSON result in the query's `json_format`. Rows and arrays are
+/// written batch by batch; a columnar result is held back until it is finished, since each
+/// column spans every batch.
+pub(crate) struct JsonWriter {
+    format: JsonFormat,
+    options: EncoderOptions,
+    /// Column names, as JSON strings.
+    names: Vec<String>,
+    buffer: Vec<u8>,
+    started: bool,
+    rows: usize,
+    columns: Vec<Vec<u8>>,
+}
+
+impl JsonWriter {
+    pub(crate) fn new(schema: &SchemaRef, params: &QueryParams) -> Result<Self> {
+        let names = schema
+            .fields()
+            .iter()
+            .map(|field| serde_json::to_string(field.name()))
+            .collect::<serde_json::Result<Vec<_>>>()?;
+
+        Ok(JsonWriter {
+            format: params.json_format.unwrap_or_default(),
+            options: match value_encoders(params)? {
+                Some(encoders) => EncoderOptions::default().with_encoder_factory(encoders),
+                None => EncoderOptions::default(),
+            },
+            columns: vec![Vec::new(); names.len()],
+            names,
+            buffer: Vec::new(),
+            started: false,
+            rows: 0,
+        })
+    }
+
+    pub(crate) fn write(&mut self, batch: &RecordBatch) -> Result<()> {
+        if batch.num_rows() == 0 {
+            return Ok(());
+        }
+
+        let JsonWriter { format, options, buffer, started, rows, columns, .. } = self;
+
+        match format {
+            JsonFormat::Rows => {
+                let array = StructArray::from(batch.clone());
+                let field = Arc::new(Field::new_struct("", batch.schema().fields().clone(), false));
+                let mut encoder = arrow_json::writer::make_encoder(&field, &array, options)?;
+
+                for row in 0..batch.num_rows() {
+                    start_row(buffer, started);
+                    encoder.encode(row, buffer);
+                }
+            }
+            JsonFormat::Arrays => {
+                let mut encoders = column_encoders(batch, options)?;
+
+                for row in 0..batch.num_rows() {
+                    start_row(buffer, started);
+                    buffer.push(b'[');
+                    for (column, encoder) in encoders.iter_mut().enumerate() {
+                        if column > 0 {
+                            buffer.push(b',');
+                        }
+                        encode_value(encoder, row, buffer);
+                    }
+                    buffer.push(b']');
+                }
+            }
+            JsonFormat::Columnar => {
+                let mut encoders = column_encoders(batch, options)?;
+
+                for (encoder, values) in encoders.iter_mut().zip(columns.iter_mut()) {
+                    for row in 0..batch.num_rows() {
+                        if *rows + row > 0 {
+                            values.push(b',');
+                        }
+                        encode_value(encoder, row, values);
+                    }
+                }
+                *rows += batch.num_rows();
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn finish(&mut self) -> Result<()> {
+        match self.format {
+            JsonFormat::Rows | JsonFormat::Arrays => {
+                if !self.started {
+                    self.buffer.push(b'[');
+                }
+                self.buffer.push(b']');
+            }
+            JsonFormat::Columnar => {
+                self.buffer.push(b'{');
+                for (column, (name, values)) in self.names.iter().zip(&self.columns).enumerate() {
+                    if column > 0 {
+                        self.buffer.push(b',');
+                    }
+                    self.buffer.extend_from_slice(name.as_bytes());
+                    self.buffer.extend_from_slice(b":[");
+                    self.buffer.extend_from_slice(values);
+                    self.buffer.push(b']');
+                }
+                self.buffer.push(b'}');
+                self.columns.clear();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The bytes written so far, to be taken out between batches.
+    pub(crate) fn get_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+}
+
+fn start_row(buffer: &mut Vec<u8>, started: &mut bool) {
+    buffer.push(if *started { b',' } else { b'[' });
+    *started = true;
+}
+
+fn column_encoders<'a>(batch: &'a RecordBatch, options: &'a EncoderOptions) -> Result<Vec<NullableEncoder<'a>>> {
+    let encoders = batch
+        .schema_ref()
+        .fields()
+        .iter()
+        .zip(batch.columns())
+        .map(|(field, column)| arrow_json::writer::make_encoder(field, column.as_ref(), options))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(encoders)
+}
+
+fn encode_value(encoder: &mut NullableEncoder, row: usize, out: &mut Vec<u8>) {
+    if encoder.is_null(row) {
+        out.extend_from_slice(b"null");
+    }
+    else {
+        encoder.encode(row, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{ArrayRef, Decimal128Array, Int64Array, StringArray, TimestampMicrosecondArray};
+    use arrow::datatypes::Schema;
+
+    fn write_json(batches: &[RecordBatch], params: serde_json::Value) -> String {
+        let mut params = params;
+        params["database"] = "test".into();
+        let params: QueryParams = serde_json::from_value(params).unwrap();
+
+        let mut writer = JsonWriter::new(&batches[0].schema(), &params).unwrap();
+        for batch in batches {
+            writer.write(batch).unwrap();
+        }
+        writer.finish().unwrap();
+
+        String::from_utf8(std::mem::take(writer.get_mut())).unwrap()
+    }
+
+    fn batch(columns: Vec<(&str, ArrayRef)>) -> RecordBatch {
+        RecordBatch::try_from_iter(columns).unwrap()
+    }
+
+    fn rows() -> Vec<RecordBatch> {
+        vec![
+            batch(vec![
+                ("i", Arc::new(Int64Array::from(vec![1, 2])) as ArrayRef),
+                ("s", Arc::new(StringArray::from(vec![Some("a"), None]))),
+            ]),
+            batch(vec![
+                ("i", Arc::new(Int64Array::from(vec![3])) as ArrayRef),
+                ("s", Arc::new(StringArray::from(vec!["c"]))),
+            ]),
+        ]
+    }
+
+    #[test]
+    fn test_rows_format() {
+        let json = write_json(&rows(), serde_json::json!({}));
+        assert_eq!(json, r#"[{"i":1,"s":"a"},{"i":2},{"i":3,"s":"c"}]"#);
+    }
+
+    #[test]
+    fn test_arrays_format() {
+        let json = write_json(&rows(), serde_json::json!({"json_format": "arrays"}));
+        assert_eq!(json, r#"[[1,"a"],[2,null],[3,"c"]]"#);
+    }
+
+    #[test]
+    fn test_columnar_format() {
+        let json = write_json(&rows(), serde_json::json!({"json_format": "columnar"}));
+        assert_eq!(json, r#"{"i":[1,2,3],"s":["a",null,"c"]}"#);
+    }
+
+    #[test]
+    fn test_empty_result() {
+        let empty = vec![RecordBatch::new_empty(rows()[0].schema())];
+
+        assert_eq!(write_json(&empty, serde_json::json!({})), "[]");
+        assert_eq!(write_json(&empty, serde_json::json!({"json_format": "arrays"})), "[]");
+        assert_eq!(write_json(&empty, serde_json::json!({"json_format": "columnar"})), r#"{"i":[],"s":[]}"#);
+    }
+
+    #[test]
+    fn test_big_numbers_as_strings() {
+        let decimals = Decimal128Array::from(vec![Some(1250), None]).with_precision_and_scale(10, 2).unwrap();
+        let batches = vec![batch(vec![
+            ("i", Arc::new(Int64Array::from(vec![9_007_199_254_740_993, 2])) as ArrayRef),
+            ("d", Arc::new(decimals)),
+        ])];
+
+        let json = write_json(&batches, serde_json::json!({"json_format": "arrays", "big_numbers_as_strings": true}));
+        assert_eq!(json, r#"[["9007199254740993","12.50"],["2",null]]"#);
+    }
+
+    #[test]
+    fn test_epoch_ms_timestamps() {
+        let timestamps = TimestampMicrosecondArray::from(vec![Some(1_706_702_400_500_000), Some(-1), None]);
+        let batches = vec![batch(vec![("ts", Arc::new(timestamps) as ArrayRef)])];
+
+        let json = write_json(&batches, serde_json::json!({"json_format": "arrays", "timestamp_format": "epoch-ms"}));
+        assert_eq!(json, "[[1706702400500],[-1],[null]]");
+    }
+
+    #[test]
+    fn test_iso_timestamps() {
+        let timestamps = TimestampMicrosecondArray::from(vec![1_706_702_400_500_000]);
+        let batches = vec![batch(vec![("ts", Arc::new(timestamps) as ArrayRef)])];
+
+        let json = write_json(&batches, serde_json::json!({"json_format": "arrays", "timestamp_format": "iso"}));
+        assert_eq!(json, r#"[["2024-01-31T12:00:00.500Z"]]"#);
+    }
+
+    #[test]
+    fn test_schema_column_names_are_escaped() {
+        let schema = Arc::new(Schema::new(vec![Field::new("a \"b\"", DataType::Int64, false)]));
+        let batches = vec![RecordBatch::try_new(schema, vec![Arc::new(Int64Array::from(vec![1]))]).unwrap()];
+
+        let json = write_json(&batches, serde_json::json!({"json_format": "columnar"}));
+        assert_eq!(json, r#"{"a \"b\"":[1]}"#);
+    }
+
+    #[test]
+    fn test_zoned_timestamps() {
+        let timestamps = TimestampMicrosecondArray::from(vec![1_706_702_400_500_000, 1_719_835_200_000_000]).with_timezone("UTC");
+        let batches = vec![batch(vec![("ts", Arc::new(timestamps) as ArrayRef)])];
+
+        let json = write_json(&batches, serde_json::json!({"json_format": "arrays", "timezone": "Europe/Berlin"}));
+        assert_eq!(json, r#"[["2024-01-31T13:00:00.500+01:00"],["2024-07-01T14:00:00+02:00"]]"#);
+    }
+
+    #[test]
+    fn test_zoned_timestamps_in_utc() {
+        let timestamps = TimestampMicrosecondArray::from(vec![1_706_702_400_500_000]).with_timezone("UTC");
+        let batches = vec![batch(vec![("ts", Arc::new(timestamps) as ArrayRef)])];
+
+        let json = write_json(&batches, serde_json::json!({"json_format": "arrays", "timezone": "UTC"}));
+        assert_eq!(json, r#"[["2024-01-31T12:00:00.500Z"]]"#);
+    }
+}
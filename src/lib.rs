@@ -1,18 +1,27 @@
 mod app;
 mod auth;
+mod bundle;
 mod cache;
+mod catalog;
 mod constants;
 mod db;
+mod envelope;
+mod explain;
 mod flight;
+mod flight_sql;
 mod interfaces;
+mod json;
 mod query;
 mod sanitize;
+mod session;
 mod sql;
 mod state;
+mod upload;
+mod ws;
 
 pub use app::app;
 pub use auth::{AuthConfig, create_auth_config, selective_auth_middleware};
-pub use cache::{get_key, retrieve};
+pub use cache::{get_key, lookup};
 pub use db::{ConnectionPool, Database};
 pub use flight::{FlightServer, serve};
 pub use interfaces::{AppError, Command, DbState, QueryParams, QueryResponse};
@@ -33,15 +33,24 @@ fn log_panic_entry(message: &str, data: serde_json::Value) {
 
 mod app;
 mod auth;
+mod bundle;
 mod cache;
+mod catalog;
 mod constants;
 mod db;
+mod envelope;
+mod explain;
 mod flight;
+mod flight_sql;
 mod interfaces;
+mod json;
 mod query;
 mod sanitize;
+mod session;
 mod sql;
 mod state;
+mod upload;
+mod ws;
 
 unsafe extern "C" {
     pub fn duckdb_library_version() -> *const std::os::raw::c_char;
@@ -323,6 +332,7 @@ async fn app_main(args: CliArgs) -> Result<(), Box<dyn std::error::Error>> {
         pool_timeout: args.pool_timeout,
         pool_idle_timeout: args.pool_idle_timeout,
         pool_max_lifetime: args.pool_max_lifetime,
+        async_result_retention: args.async_result_retention,
     };
 
     let app_state = Arc::new(AppState {
@@ -330,6 +340,10 @@ async fn app_main(args: CliArgs) -> Result<(), Box<dyn std::error::Error>> {
         root: root.clone(),
         states: Mutex::new(HashMap::new()),
         running_queries: Mutex::new(HashMap::new()),
+        flight_tickets: Mutex::new(HashMap::new()),
+        async_queries: Mutex::new(HashMap::new()),
+        cursors: Mutex::new(HashMap::new()),
+        sessions: Mutex::new(HashMap::new()),
     });
 
     let fmt_layer = tracing_subscriber::fmt::layer()
@@ -374,7 +388,7 @@ async fn app_main(args: CliArgs) -> Result<(), Box<dyn std::error::Error>> {
         None
     };
 
-    let app = app::app(app_state.clone(), args.timeout, auth_config).await?;
+    let app = app::app(app_state.clone(), args.timeout, auth_config.clone()).await?;
 
     let addr = SocketAddr::new(args.address, args.http_port);
     let mut listenfd = ListenFd::from_env();
@@ -391,11 +405,16 @@ async fn app_main(args: CliArgs) -> Result<(), Box<dyn std::error::Error>> {
     let flight_state = app_state.clone();
     let flight_cancel_clone = flight_cancel.clone();
     let flight_handle = tokio::spawn(async move {
-        if let Err(e) = flight::serve(flight_addr, flight_state, flight_cancel_clone).await {
+        if let Err(e) = flight::serve(flight_addr, flight_state, auth_config, flight_cancel_clone).await {
             tracing::error!("Flight server failed: {}", e);
         }
     });
 
+    let sweep_cancel = tokio_util::sync::CancellationToken::new();
+    let sweep_state = app_state.clone();
+    let sweep_cancel_clone = sweep_cancel.clone();
+    let sweep_handle = tokio::spawn(async move { sweep_state.sweep_expired(sweep_cancel_clone).await });
+
     let memory_monitor_cancel = tokio_util::sync::CancellationToken::new();
     let memory_monitor_handle = tokio::spawn(monitor_memory_pressure(
         args.memory_pressure_warn,
@@ -415,6 +434,7 @@ async fn app_main(args: CliArgs) -> Result<(), Box<dyn std::error::Error>> {
             shutdown_signal().await;
 
             flight_cancel.cancel();
+            sweep_cancel.cancel();
             memory_monitor_cancel.cancel();
 
             tracing::debug!("Starting 5s shutdown timeout");
@@ -430,6 +450,9 @@ async fn app_main(args: CliArgs) -> Result<(), Box<dyn std::error::Error>> {
     let _ = flight_handle.await;
     tracing::debug!("Flight server stopped");
 
+    let _ = sweep_handle.await;
+    tracing::debug!("Expiry sweep stopped");
+
     let _ = memory_monitor_handle.await;
     tracing::debug!("Memory pressure monitor stopped");
 
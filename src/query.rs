@@ -1,16 +1,355 @@
 use std::future::Future;
+use std::collections::{BTreeMap, HashMap};
 use std::pin::Pin;
+use std::sync::Arc;
 
-use crate::cache::retrieve;
-use crate::constants::{RETRIABLE_ERRORS, TIMEOUT_ERRORS};
-use crate::interfaces::{AppError, Command, QueryInfo, QueryParams, QueryResponse};
-use crate::state::AppState;
+use crate::bundle;
+use crate::envelope;
+use crate::explain;
+use crate::json::{JsonWriter, ndjson_writer};
+use crate::session;
+use crate::cache::{get_key, lookup};
+use crate::constants::{MAX_CACHED_RESULT_BYTES, RETRIABLE_ERRORS, TIMEOUT_ERRORS};
+use crate::db::RecordBatchStream;
+use crate::sql::{has_order_by, needs_query_limit, page_sql};
+use crate::interfaces::{
+    AppError, ArrowCompression, ArrowFormat, AsyncQueryInfo, AsyncResultParams, Command, DbState, PrepareParams,
+    QueryInfo, QueryParams, QueryResponse, ResultStream,
+};
+use crate::state::{AppState, AsyncQuery, AsyncQueryStatus, RunningQuery};
+use anyhow::Result;
+use arrow::{array::timezone::Tz, datatypes::SchemaRef, record_batch::RecordBatch};
+use arrow_ipc::{
+    CompressionType,
+    writer::{FileWriter, IpcWriteOptions, StreamWriter},
+};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{Compression, ZstdLevel},
+    file::properties::WriterProperties,
+};
+use axum::body::Bytes;
+use futures::{StreamExt, TryStreamExt};
+use serde::Serialize;
+use std::time::{Instant, SystemTime};
 use tokio::time::{Duration, sleep};
+use tokio_util::sync::CancellationToken;
 
-pub async fn with_db_retry<F>(state: &AppState, params: &QueryParams, query_fn: F) -> Result<QueryResponse, AppError>
+/// Removes a streamed query from `running_queries` once its response stream is dropped.
+/// If the client disconnected before the stream finished, the query is cancelled too.
+pub(crate) struct RunningQueryGuard {
+    pub(crate) state: Arc<AppState>,
+    pub(crate) query_id: String,
+    pub(crate) cancel_token: CancellationToken,
+}
+
+impl Drop for RunningQueryGuard {
+    fn drop(&mut self) {
+        // Cancelling an already finished query is a no-op.
+        self.cancel_token.cancel();
+
+        // Only the cancelled entry is ours; a client may have reused the ID for a new query.
+        fn remove_finished(queries: &mut HashMap<String, RunningQuery>, query_id: &str) {
+            if queries.get(query_id).is_some_and(|query| query.cancel_token.is_cancelled()) {
+                queries.remove(query_id);
+            }
+        }
+
+        let query_id = std::mem::take(&mut self.query_id);
+        if let Ok(mut queries) = self.state.running_queries.try_lock() {
+            remove_finished(&mut queries, &query_id);
+        }
+        else if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let state = Arc::clone(&self.state);
+            runtime.spawn(async move {
+                remove_finished(&mut *state.running_queries.lock().await, &query_id);
+            });
+        }
+    }
+}
+
+/// Writes record batches as response body chunks: Arrow IPC stream messages, an Arrow IPC
+/// file, pieces of a JSON result, CSV or NDJSON lines, or Parquet row groups.
+enum BodyWriter {
+    ArrowStream(StreamWriter<Vec<u8>>),
+    ArrowFile(FileWriter<Vec<u8>>),
+    Json(JsonWriter),
+    /// The CSV writer does not expose its buffer, so each batch gets a writer of its own
+    /// and only the first one writes the header.
+    Csv { schema: SchemaRef, buffer: Vec<u8>, header: bool },
+    Ndjson(arrow_json::LineDelimitedWriter<Vec<u8>>),
+    Parquet(ArrowWriter<Vec<u8>>),
+}
+
+impl BodyWriter {
+    fn new(command: &Command, schema: &SchemaRef, params: &QueryParams) -> Result<Self> {
+        let compression = params.compression.map(|compression| match compression {
+            ArrowCompression::Lz4Frame => CompressionType::LZ4_FRAME,
+            ArrowCompression::Zstd => CompressionType::ZSTD,
+        });
+        let options = IpcWriteOptions::default().try_with_compression(compression)?;
+
+        match (command, params.arrow_format.unwrap_or_default()) {
+            (Command::Arrow, ArrowFormat::Stream) => Ok(BodyWriter::ArrowStream(
+                StreamWriter::try_new_with_options(Vec::new(), schema, options)?,
+            )),
+            (Command::Arrow, ArrowFormat::File) => Ok(BodyWriter::ArrowFile(
+                FileWriter::try_new_with_options(Vec::new(), schema, options)?,
+            )),
+            (Command::Json, _) => Ok(BodyWriter::Json(JsonWriter::new(schema, params)?)),
+            (Command::Csv, _) => Ok(BodyWriter::Csv {
+                schema: Arc::clone(schema),
+                buffer: Vec::new(),
+                header: true,
+            }),
+            (Command::Ndjson, _) => Ok(BodyWriter::Ndjson(ndjson_writer(params)?)),
+            (Command::Parquet, _) => {
+                let properties = WriterProperties::builder()
+                    .set_compression(Compression::ZSTD(ZstdLevel::default()))
+                    .build();
+                Ok(BodyWriter::Parquet(ArrowWriter::try_new(Vec::new(), Arc::clone(schema), Some(properties))?))
+            }
+            (Command::Exec | Command::Explain | Command::CreateBundle | Command::LoadBundle, _) => {
+                Err(anyhow::anyhow!("{:?} results have no body to stream", command))
+            }
+        }
+    }
+
+    fn write(&mut self, batch: &RecordBatch) -> Result<()> {
+        match self {
+            BodyWriter::ArrowStream(writer) => writer.write(batch)?,
+            BodyWriter::ArrowFile(writer) => writer.write(batch)?,
+            BodyWriter::Json(writer) => writer.write(batch)?,
+            BodyWriter::Csv { buffer, header, .. } => {
+                let mut writer = arrow::csv::WriterBuilder::new().with_header(*header).build(buffer);
+                writer.write(batch)?;
+                *header = false;
+            }
+            BodyWriter::Ndjson(writer) => writer.write(batch)?,
+            BodyWriter::Parquet(writer) => writer.write(batch)?,
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        match self {
+            BodyWriter::ArrowStream(writer) => writer.finish()?,
+            BodyWriter::ArrowFile(writer) => writer.finish()?,
+            BodyWriter::Json(writer) => writer.finish()?,
+            // An empty result still gets its header line
+            BodyWriter::Csv { schema, header: true, .. } => {
+                let batch = RecordBatch::new_empty(Arc::clone(schema));
+                self.write(&batch)?;
+            }
+            BodyWriter::Csv { .. } => {}
+            BodyWriter::Ndjson(writer) => writer.finish()?,
+            BodyWriter::Parquet(writer) => {
+                writer.finish()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Takes the bytes written since the last call.
+    fn take(&mut self) -> Bytes {
+        let buffer = match self {
+            BodyWriter::ArrowStream(writer) => writer.get_mut(),
+            BodyWriter::ArrowFile(writer) => writer.get_mut(),
+            BodyWriter::Json(writer) => writer.get_mut(),
+            BodyWriter::Csv { buffer, .. } => buffer,
+            BodyWriter::Ndjson(writer) => writer.get_mut(),
+            BodyWriter::Parquet(writer) => writer.inner_mut(),
+        };
+        Bytes::from(std::mem::take(buffer))
+    }
+}
+
+/// Keeps a copy of the body for the cache until it grows past `MAX_CACHED_RESULT_BYTES`.
+pub(crate) fn keep_for_cache(cached: &mut Option<Vec<u8>>, chunk: &[u8]) {
+    if let Some(body) = cached {
+        if body.len() + chunk.len() > MAX_CACHED_RESULT_BYTES {
+            *cached = None;
+        }
+        else {
+            body.extend_from_slice(chunk);
+        }
+    }
+}
+
+/// The first `rows` rows of a result.
+pub(crate) fn take_rows(batches: &[RecordBatch], rows: usize) -> Vec<RecordBatch> {
+    let mut remaining = rows;
+    batches
+        .iter()
+        .map(|batch| {
+            let length = batch.num_rows().min(remaining);
+            remaining -= length;
+            batch.slice(0, length)
+        })
+        .collect()
+}
+
+/// Sends a CSV, NDJSON or Parquet result as an attachment named after the query's `name`.
+fn download(command: &Command, params: &QueryParams, body: ResultStream) -> Result<QueryResponse> {
+    let (content_type, extension) = match command {
+        Command::Csv => ("text/csv", "csv"),
+        Command::Ndjson => ("application/x-ndjson", "ndjson"),
+        Command::Parquet => ("application/vnd.apache.parquet", "parquet"),
+        _ => return Err(anyhow::anyhow!("{:?} results are not sent as downloads", command)),
+    };
+
+    let name: String = params
+        .name
+        .as_deref()
+        .unwrap_or("result")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+
+    Ok(QueryResponse::Download {
+        body,
+        content_type,
+        filename: format!("{}.{}", name, extension),
+    })
+}
+
+/// Wraps a body that is already complete, e.g. a cached one, in the response for its command.
+pub(crate) fn buffered_response(command: &Command, params: &QueryParams, body: Vec<u8>) -> Result<QueryResponse> {
+    match command {
+        Command::Arrow => Ok(QueryResponse::Arrow(body, params.arrow_format.unwrap_or_default())),
+        Command::Json => Ok(QueryResponse::Json(String::from_utf8(body)?)),
+        _ => download(command, params, futures::stream::iter([Ok(Bytes::from(body))]).boxed()),
+    }
+}
+
+/// Encodes a result that is already in memory.
+pub(crate) fn encode_batches(command: &Command, params: &QueryParams, schema: &SchemaRef, batches: &[RecordBatch]) -> Result<Vec<u8>> {
+    let mut writer = BodyWriter::new(command, schema, params)?;
+    let mut body = Vec::new();
+
+    for batch in batches {
+        writer.write(batch)?;
+        body.extend_from_slice(&writer.take());
+    }
+    writer.finish()?;
+    body.extend_from_slice(&writer.take());
+
+    Ok(body)
+}
+
+/// Encodes a query result as it comes out of the database. With a `cache_key`, the body is
+/// cached once the stream completes, provided it stayed under `MAX_CACHED_RESULT_BYTES`.
+fn stream_body(
+    command: &Command,
+    params: &QueryParams,
+    result: RecordBatchStream,
+    db_state: Arc<DbState>,
+    cache_key: Option<String>,
+    guard: RunningQueryGuard,
+) -> Result<ResultStream> {
+    let RecordBatchStream { schema, mut batches } = result;
+    let mut writer = BodyWriter::new(command, &schema, params)?;
+
+    let stream = async_stream::try_stream! {
+        // The query stays listed as running until the body is sent or the client goes away
+        let _guard = guard;
+        let mut cached = cache_key.as_ref().map(|_| Vec::new());
+
+        loop {
+            let batch = batches.next().await.transpose()?;
+            match &batch {
+                Some(batch) => writer.write(batch)?,
+                None => writer.finish()?,
+            }
+
+            let chunk = writer.take();
+            if !chunk.is_empty() {
+                keep_for_cache(&mut cached, &chunk);
+                yield chunk;
+            }
+
+            if batch.is_none() {
+                break;
+            }
+        }
+
+        if let (Some(key), Some(body)) = (cache_key, cached) {
+            db_state.cache.lock().await.put(key, body);
+        }
+    };
+
+    Ok(Box::pin(stream))
+}
+
+/// The key a query result is cached under. Arrow results are cached per encoding, JSON
+/// results per format and envelope results apart from plain ones, since each one is a
+/// different body.
+pub(crate) fn result_cache_key(sql: &str, params: &QueryParams, command: &Command) -> String {
+    let mut key = get_key(sql, &params.args, &params.named_args, command);
+    if has_envelope(params, command) {
+        key.push_str(".envelope");
+    }
+    // The time zone changes what DuckDB computes, not only how results are written
+    if let Some(timezone) = &params.timezone {
+        key.push_str(&format!(".tz={}", timezone));
+    }
+    match command {
+        Command::Arrow => format!(
+            "{}.{:?}.{:?}",
+            key,
+            params.arrow_format.unwrap_or_default(),
+            params.compression
+        ),
+        Command::Json | Command::Ndjson
+            if params.json_format.is_some()
+                || params.big_numbers_as_strings.is_some()
+                || params.timestamp_format.is_some() =>
+        {
+            format!(
+                "{}.{:?}.{:?}.{:?}",
+                key, params.json_format, params.big_numbers_as_strings, params.timestamp_format
+            )
+        }
+        _ => key,
+    }
+}
+
+/// Rejects a `timezone` that neither DuckDB nor the JSON writer would know.
+fn check_timezone(params: &QueryParams) -> Result<(), AppError> {
+    match &params.timezone {
+        Some(timezone) if timezone.parse::<Tz>().is_err() => {
+            Err(AppError::BadRequest(anyhow::anyhow!("Unknown time zone: {}", timezone).into()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Refuses arguments that cannot be bound before the query starts.
+pub(crate) fn check_args(params: &QueryParams) -> Result<(), AppError> {
+    let args = params.args.iter().flatten();
+    let named_args = params.named_args.iter().flat_map(BTreeMap::values);
+
+    for arg in args.chain(named_args) {
+        if arg.is_bindable() {
+            arg.as_tosql().map_err(|e| AppError::BadRequest(e.into()))?;
+        }
+        else {
+            arg.sql_literal().map_err(|e| AppError::BadRequest(e.into()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the result is sent with an envelope; only `arrow` and `json` results have one.
+fn has_envelope(params: &QueryParams, command: &Command) -> bool {
+    params.envelope.unwrap_or(false) && matches!(command, Command::Arrow | Command::Json)
+}
+
+pub async fn with_db_retry<F>(state: &Arc<AppState>, params: &QueryParams, query_fn: F) -> Result<QueryResponse, AppError>
 where
     F: for<'a> Fn(
-        &'a AppState,
+        &'a Arc<AppState>,
         &'a QueryParams,
     ) -> Pin<Box<dyn Future<Output = Result<QueryResponse, AppError>> + Send + 'a>>,
 {
@@ -72,11 +411,150 @@ where
     }
 }
 
-pub async fn handle(state: &AppState, params: &QueryParams) -> Result<QueryResponse, AppError> {
+/// Adds a query to `running_queries`, under the client's `query_id` if it picked one.
+pub(crate) async fn register_query(
+    state: &AppState,
+    params: &QueryParams,
+    sql: &str,
+) -> Result<(String, CancellationToken), AppError> {
+    match &params.query_id {
+        Some(query_id) => {
+            let cancel_token = state
+                .start_query_with_id(query_id.clone(), params.database.clone(), sql.to_string())
+                .await?;
+            Ok((query_id.clone(), cancel_token))
+        }
+        None => Ok(state.start_query(params.database.clone(), sql.to_string()).await),
+    }
+}
+
+/// Runs one page of a paged query. The first request opens a cursor and every later one
+/// re-runs the query from where the previous page ended, so the query needs an ORDER BY.
+async fn paginate(state: &Arc<AppState>, params: &QueryParams) -> Result<QueryResponse, AppError> {
+    let cursor = match &params.cursor {
+        Some(id) => state
+            .get_cursor(id)
+            .await
+            .ok_or_else(|| AppError::BadRequest(anyhow::anyhow!("Cursor not found or expired: {}", id).into()))?,
+        None => {
+            let pageable = matches!(
+                params.query_type,
+                Some(Command::Arrow | Command::Json | Command::Csv | Command::Ndjson | Command::Parquet)
+            );
+            let sql = params.sql.as_deref().unwrap_or_default();
+            if !pageable || page_sql(sql, 1, 0).is_none() {
+                return Err(AppError::BadRequest(
+                    anyhow::anyhow!("Only a single query returning results can be paged").into(),
+                ));
+            }
+            // Every page runs the query again, so without an order rows could move between pages
+            if !has_order_by(sql) {
+                return Err(AppError::BadRequest(
+                    anyhow::anyhow!("Paged queries need an ORDER BY").into(),
+                ));
+            }
+            check_timezone(params)?;
+            check_args(params)?;
+
+            if params.create.unwrap_or(false) {
+                state.create_database_if_not_exists(&params.database).await?;
+            }
+            state.open_cursor(params.clone()).await
+        }
+    };
+
+    let cursor_params = &cursor.params;
+    let command = cursor_params.query_type.clone().unwrap_or(Command::Arrow);
+    let page_size = params
+        .page_size
+        .or(cursor_params.page_size)
+        .unwrap_or(state.defaults.row_limit)
+        .min(state.defaults.row_limit);
+
+    // One extra row tells whether another page follows
+    let sql = cursor_params
+        .sql
+        .as_deref()
+        .and_then(|sql| page_sql(sql, page_size + 1, cursor.offset))
+        .unwrap_or_default();
+
+    let db_state = state
+        .get_or_create_db_state(
+            &cursor_params.database,
+            &cursor_params.extensions,
+            &cursor_params.secrets,
+            &cursor_params.ducklakes
+        )
+        .await?;
+
+    let (query_id, cancel_token) = register_query(state, params, &sql).await?;
+    let _guard = RunningQueryGuard {
+        state: Arc::clone(state),
+        query_id: query_id.clone(),
+        cancel_token: cancel_token.clone(),
+    };
+
+    tracing::info!("Cursor: '{}', Offset: {}, Query ID: '{}'", cursor.id, cursor.offset, query_id);
+
+    let RecordBatchStream { schema, batches } = db_state
+        .db
+        .stream_record_batches(
+            &sql,
+            &cursor_params.args,
+            &cursor_params.named_args,
+            &cursor_params.prepare_sql,
+            &cursor_params.default_schema,
+            &cursor_params.timezone,
+            page_size + 1,
+            &cursor_params.extensions,
+            &cursor_params.secrets,
+            &cursor_params.ducklakes,
+            None,
+            &cancel_token,
+        )
+        .await?;
+    let batches: Vec<RecordBatch> = batches.try_collect().await?;
+
+    let rows: usize = batches.iter().map(|batch| batch.num_rows()).sum();
+    let has_more = rows > page_size;
+
+    let page = take_rows(&batches, page_size);
+
+    let body = encode_batches(&command, cursor_params, &schema, &page)?;
+    let result = buffered_response(&command, cursor_params, body)?;
+
+    if has_more {
+        state.advance_cursor(&cursor.id, cursor.offset + page_size).await;
+    }
+    else {
+        state.close_cursor(&cursor.id).await;
+    }
+
+    Ok(QueryResponse::QueryWithId {
+        query_id,
+        result: Box::new(QueryResponse::Page {
+            cursor: has_more.then_some(cursor.id),
+            has_more,
+            result: Box::new(result),
+        }),
+    })
+}
+
+pub async fn handle(state: &Arc<AppState>, params: &QueryParams) -> Result<QueryResponse, AppError> {
+    if let Some(session_id) = &params.session_id {
+        return session::handle(state, session_id, params).await;
+    }
+
+    if params.cursor.is_some() || params.page_size.is_some() {
+        return paginate(state, params).await;
+    }
+
     let command = &params.query_type;
     if command.is_none() {
         return Err(AppError::BadRequest(anyhow::anyhow!("Query type is required").into()));
     }
+    check_timezone(params)?;
+    check_args(params)?;
 
     if params.create.unwrap_or(false) {
         state.create_database_if_not_exists(&params.database).await?;
@@ -86,6 +564,12 @@ pub async fn handle(state: &AppState, params: &QueryParams) -> Result<QueryRespo
         }
     }
 
+    match command {
+        Some(Command::CreateBundle) => return bundle::create(state, params).await,
+        Some(Command::LoadBundle) => return bundle::load(state, params).await,
+        _ => {}
+    }
+
     let sql = params.sql.clone().ok_or_else(|| {
         AppError::BadRequest(anyhow::anyhow!("SQL query is required").into())
     })?;
@@ -105,7 +589,15 @@ pub async fn handle(state: &AppState, params: &QueryParams) -> Result<QueryRespo
         )
         .await?;
 
-    let (query_id, cancel_token) = state.start_query(params.database.clone(), sql.clone()).await;
+    let started = Instant::now();
+    let (query_id, cancel_token) = register_query(state, params, &sql).await?;
+
+    // Streamed responses take the guard along; otherwise the query is done when this returns
+    let guard = RunningQueryGuard {
+        state: Arc::clone(state),
+        query_id: query_id.clone(),
+        cancel_token: cancel_token.clone(),
+    };
 
     tracing::info!(
         "Command: '{:?}', Query ID: '{}', Params: '{:?}'",
@@ -115,93 +607,500 @@ pub async fn handle(state: &AppState, params: &QueryParams) -> Result<QueryRespo
     );
 
     let result = match command {
-        Some(Command::Arrow) => {
+        Some(command @ (Command::Arrow | Command::Json | Command::Csv | Command::Ndjson | Command::Parquet)) => {
             let persist = params.persist.unwrap_or(false);
             let invalidate = params.invalidate.unwrap_or(false);
             let limit = params.limit.unwrap_or(state.defaults.row_limit);
-            let buffer = retrieve(
-                &db_state.cache,
-                sql.as_str(),
-                &params.args,
-                &Command::Arrow,
-                persist,
-                invalidate,
-                || {
-                    db_state.db.get_arrow(
-                        &sql,
-                        &params.args,
-                        &params.prepare_sql,
-                        &params.default_schema,
-                        limit,
-                        &params.extensions,
-                        &params.secrets,
-                        &params.ducklakes,
-                        &cancel_token,
-                    )
-                },
-            )
-            .await?;
-            Ok(QueryResponse::Arrow(buffer))
+            let arrow_format = params.arrow_format.unwrap_or_default();
+
+            let key = result_cache_key(&sql, params, command);
+            let envelope = has_envelope(params, command);
+
+            let prepared = match params.prepared.as_deref() {
+                Some(name) => db_state.db.get_prepared_statement(name).await?.filter(|statement| statement.sql == sql),
+                None => None,
+            };
+
+            match lookup(&db_state.cache, &key, invalidate).await {
+                Some(cached) if envelope => envelope::from_cache(command, params, &query_id, cached, started),
+                Some(cached) => Ok(buffered_response(command, params, cached)?),
+                None if envelope => {
+                    // One extra row tells whether the limit cut the result off
+                    let result = db_state
+                        .db
+                        .stream_record_batches(
+                            &sql,
+                            &params.args,
+                            &params.named_args,
+                            &params.prepare_sql,
+                            &params.default_schema,
+                            &params.timezone,
+                            limit + 1,
+                            &params.extensions,
+                            &params.secrets,
+                            &params.ducklakes,
+                            prepared.clone(),
+                            &cancel_token,
+                        )
+                        .await?;
+
+                    let cache = persist.then(|| (Arc::clone(&db_state), key));
+                    let limit = needs_query_limit(&sql).then_some(limit);
+                    envelope::respond(command, params, result, limit, cache, guard, started).await
+                }
+                None => {
+                    let result = db_state
+                        .db
+                        .stream_record_batches(
+                            &sql,
+                            &params.args,
+                            &params.named_args,
+                            &params.prepare_sql,
+                            &params.default_schema,
+                            &params.timezone,
+                            limit,
+                            &params.extensions,
+                            &params.secrets,
+                            &params.ducklakes,
+                            prepared.clone(),
+                            &cancel_token,
+                        )
+                        .await?;
+
+                    let cache_key = persist.then_some(key);
+                    let body = stream_body(command, params, result, Arc::clone(&db_state), cache_key, guard)?;
+
+                    match command {
+                        Command::Arrow => Ok(QueryResponse::ArrowStream(body, arrow_format)),
+                        Command::Json => Ok(QueryResponse::JsonStream(body)),
+                        _ => Ok(download(command, params, body)?),
+                    }
+                }
+            }
         }
         Some(Command::Exec) => {
-            db_state.db.execute(sql.as_str(), &params.default_schema, &params.extensions).await?;
+            db_state
+                .db
+                .execute(sql.as_str(), &[], &params.default_schema, &params.extensions, &cancel_token)
+                .await?;
             Ok(QueryResponse::Empty)
         }
-        Some(Command::Json) => {
-            let persist = params.persist.unwrap_or(false);
-            let invalidate = params.invalidate.unwrap_or(false);
+        Some(Command::Explain) => {
             let limit = params.limit.unwrap_or(state.defaults.row_limit);
-            let json: Vec<u8> = retrieve(
-                &db_state.cache,
-                sql.as_str(),
-                &params.args,
-                &Command::Json,
-                persist,
-                invalidate,
-                || {
-                    db_state.db.get_json(
-                        &sql,
-                        &params.args,
-                        &params.prepare_sql,
-                        &params.default_schema,
-                        limit,
-                        &params.extensions,
-                        &params.secrets,
-                        &params.ducklakes,
-                        &cancel_token,
-                    )
-                },
-            )
-            .await?;
-
-            let string = if json.is_empty() {
-                "[]".to_string()
-            }
-            else {
-                String::from_utf8(json)?
-            };
-
-            Ok(QueryResponse::Json(string))
+            explain::run(&db_state, params, &sql, limit, &cancel_token).await
+        }
+        Some(Command::CreateBundle | Command::LoadBundle) => {
+            unreachable!("Bundle commands return before a query is started")
         }
         None => unreachable!("HOLY MOLLY, this should never happen: query type is required"),
     };
 
-    let final_result = match result {
+    match result {
         Ok(response) => Ok(QueryResponse::QueryWithId {
-            query_id: query_id.clone(),
+            query_id,
             result: Box::new(response),
         }),
         Err(e) => Err(e),
+    }
+}
+
+/// Registers a query to run in the background and answers with its status right away, so
+/// long queries are not cut off by the request timeout.
+pub async fn submit(state: &Arc<AppState>, params: QueryParams) -> Result<QueryResponse, AppError> {
+    match &params.query_type {
+        Some(Command::Arrow | Command::Json | Command::Csv | Command::Ndjson | Command::Parquet | Command::Exec) => {}
+        Some(command) => {
+            return Err(AppError::BadRequest(
+                anyhow::anyhow!("{:?} queries cannot run asynchronously", command).into(),
+            ));
+        }
+        None => return Err(AppError::BadRequest(anyhow::anyhow!("Query type is required").into())),
+    }
+
+    let sql = params
+        .sql
+        .clone()
+        .filter(|sql| !sql.trim().is_empty())
+        .ok_or_else(|| AppError::BadRequest(anyhow::anyhow!("SQL query is required").into()))?;
+    check_timezone(&params)?;
+    check_args(&params)?;
+
+    let (query_id, cancel_token) = register_query(state, &params, &sql).await?;
+
+    let query = AsyncQuery {
+        id: query_id.clone(),
+        params,
+        submitted_at: SystemTime::now(),
+        finished_at: None,
+        rows: 0,
+        status: AsyncQueryStatus::Queued,
     };
+    let info = async_query_info(&query);
+    state.submit_async_query(query).await;
+
+    tracing::info!("Submitted async query {}", query_id);
+    tokio::spawn(run_async_query(Arc::clone(state), query_id, cancel_token));
+
+    Ok(QueryResponse::AsyncQuery(info))
+}
+
+/// Runs an async query to completion and keeps its batches until the result expires.
+async fn run_async_query(state: Arc<AppState>, query_id: String, cancel_token: CancellationToken) {
+    let _guard = RunningQueryGuard {
+        state: Arc::clone(&state),
+        query_id: query_id.clone(),
+        cancel_token: cancel_token.clone(),
+    };
+
+    let Some(query) = state.get_async_query(&query_id).await else {
+        return;
+    };
+    let params = &query.params;
+
+    let run = async {
+        if params.create.unwrap_or(false) {
+            state.create_database_if_not_exists(&params.database).await?;
+        }
+
+        let db_state = state
+            .get_or_create_db_state(&params.database, &params.extensions, &params.secrets, &params.ducklakes)
+            .await?;
+
+        state
+            .update_async_query(&query_id, |query| query.status = AsyncQueryStatus::Running)
+            .await;
+
+        let sql = params.sql.clone().unwrap_or_default();
+
+        if let Some(Command::Exec) = params.query_type {
+            db_state
+                .db
+                .execute(&sql, &[], &params.default_schema, &params.extensions, &cancel_token)
+                .await?;
+            return Ok((Arc::new(arrow::datatypes::Schema::empty()), Vec::new()));
+        }
+
+        let RecordBatchStream { schema, mut batches } = db_state
+            .db
+            .stream_record_batches(
+                &sql,
+                &params.args,
+                &params.named_args,
+                &params.prepare_sql,
+                &params.default_schema,
+                &params.timezone,
+                params.limit.unwrap_or(state.defaults.row_limit),
+                &params.extensions,
+                &params.secrets,
+                &params.ducklakes,
+                None,
+                &cancel_token,
+            )
+            .await?;
+
+        let mut collected = Vec::new();
+        while let Some(batch) = batches.next().await.transpose()? {
+            let rows = batch.num_rows();
+            state.update_async_query(&query_id, |query| query.rows += rows).await;
+            collected.push(batch);
+        }
+
+        Ok::<_, AppError>((schema, collected))
+    };
+
+    // A query cancelled while it was queued never starts
+    let result = if cancel_token.is_cancelled() {
+        Err(AppError::BadRequest(anyhow::anyhow!("Query was cancelled").into()))
+    }
+    else {
+        run.await
+    };
+
+    let status = match result {
+        _ if cancel_token.is_cancelled() => AsyncQueryStatus::Cancelled,
+        Ok((schema, batches)) => AsyncQueryStatus::Done {
+            schema,
+            batches: Arc::new(batches),
+        },
+        Err(e) => {
+            tracing::warn!("Async query {} failed: {}", query_id, e);
+            AsyncQueryStatus::Failed(e.to_string())
+        }
+    };
+
+    tracing::info!("Async query {} is {}", query_id, status.name());
+    state
+        .update_async_query(&query_id, |query| {
+            query.status = status;
+            query.finished_at = Some(SystemTime::now());
+        })
+        .await;
+}
+
+fn async_query_info(query: &AsyncQuery) -> AsyncQueryInfo {
+    let elapsed = query
+        .finished_at
+        .unwrap_or_else(SystemTime::now)
+        .duration_since(query.submitted_at)
+        .unwrap_or_default();
+
+    AsyncQueryInfo {
+        query_id: query.id.clone(),
+        database: query.params.database.clone(),
+        sql: query.params.sql.clone().unwrap_or_default(),
+        status: query.status.name(),
+        submitted_at: query
+            .submitted_at
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            .to_string(),
+        elapsed_ms: elapsed.as_millis() as u64,
+        rows: query.rows,
+        error: match &query.status {
+            AsyncQueryStatus::Failed(error) => Some(error.clone()),
+            _ => None,
+        },
+    }
+}
+
+async fn find_async_query(state: &AppState, query_id: &str) -> Result<AsyncQuery, AppError> {
+    state
+        .get_async_query(query_id)
+        .await
+        .ok_or_else(|| AppError::BadRequest(anyhow::anyhow!("Async query not found: {}", query_id).into()))
+}
+
+pub async fn async_query_status(state: &AppState, query_id: &str) -> Result<QueryResponse, AppError> {
+    let query = find_async_query(state, query_id).await?;
+    Ok(QueryResponse::AsyncQuery(async_query_info(&query)))
+}
+
+/// Encodes the kept result of a finished async query, by default in the format it was
+/// submitted with.
+pub async fn async_query_result(
+    state: &AppState,
+    query_id: &str,
+    options: AsyncResultParams,
+) -> Result<QueryResponse, AppError> {
+    let query = find_async_query(state, query_id).await?;
+
+    let (schema, batches) = match query.status {
+        AsyncQueryStatus::Done { schema, batches } => (schema, batches),
+        AsyncQueryStatus::Failed(error) => return Err(AppError::Error(anyhow::anyhow!(error).into())),
+        status => {
+            return Err(AppError::BadRequest(
+                anyhow::anyhow!("Query {} is {}", query_id, status.name()).into(),
+            ));
+        }
+    };
+
+    let mut params = query.params;
+    params.query_type = options.query_type.or(params.query_type);
+    params.name = options.name.or(params.name);
+    params.arrow_format = options.arrow_format.or(params.arrow_format);
+    params.compression = options.compression.or(params.compression);
+
+    let result = match &params.query_type {
+        Some(command @ (Command::Arrow | Command::Json | Command::Csv | Command::Ndjson | Command::Parquet)) => {
+            let body = encode_batches(command, &params, &schema, &batches)?;
+            buffered_response(command, &params, body)?
+        }
+        Some(Command::Exec) | None => QueryResponse::Empty,
+        Some(command) => {
+            return Err(AppError::BadRequest(
+                anyhow::anyhow!("Results cannot be fetched as {:?}", command).into(),
+            ));
+        }
+    };
+
+    Ok(QueryResponse::QueryWithId {
+        query_id: query.id,
+        result: Box::new(result),
+    })
+}
 
-    {
-        let mut queries = state.running_queries.lock().await;
-        queries.remove(&query_id);
+#[derive(Serialize)]
+struct ColumnInfo {
+    name: String,
+    data_type: String,
+    nullable: bool,
+}
+
+#[derive(Serialize)]
+pub struct PreparedStatementInfo {
+    name: String,
+    sql: String,
+    parameter_count: usize,
+    columns: Vec<ColumnInfo>,
+}
+
+/// Registers a named statement for a database and describes its parameters and result.
+pub async fn prepare(state: &AppState, params: PrepareParams) -> Result<PreparedStatementInfo, AppError> {
+    let db_state = state
+        .get_or_create_db_state(&params.database, &None, &None, &None)
+        .await?;
+
+    let statement = db_state
+        .db
+        .prepare_statement(&params.name, &params.sql, &params.default_schema)
+        .await?;
+
+    tracing::info!(
+        "Prepared statement {} for database {} with {} parameters",
+        statement.name,
+        params.database,
+        statement.parameter_count
+    );
+
+    Ok(PreparedStatementInfo {
+        name: statement.name,
+        sql: statement.sql,
+        parameter_count: statement.parameter_count,
+        columns: statement
+            .schema
+            .fields()
+            .iter()
+            .map(|field| ColumnInfo {
+                name: field.name().clone(),
+                data_type: field.data_type().to_string(),
+                nullable: field.is_nullable(),
+            })
+            .collect(),
+    })
+}
+
+/// Runs a registered statement with the request's `args` or `named_args`, as an `arrow` query unless the
+/// request names another type.
+pub async fn execute_prepared(state: &Arc<AppState>, name: &str, mut params: QueryParams) -> Result<QueryResponse, AppError> {
+    let db_state = state
+        .get_or_create_db_state(
+            &params.database,
+            &params.extensions,
+            &params.secrets,
+            &params.ducklakes
+        )
+        .await?;
+
+    let statement = db_state
+        .db
+        .get_prepared_statement(name)
+        .await?
+        .ok_or_else(|| AppError::BadRequest(anyhow::anyhow!("Prepared statement not found: {}", name).into()))?;
+
+    let arg_count = params.args.as_ref().map_or(0, Vec::len) + params.named_args.as_ref().map_or(0, BTreeMap::len);
+    if arg_count != statement.parameter_count {
+        return Err(AppError::BadRequest(
+            anyhow::anyhow!(
+                "Prepared statement {} takes {} arguments but got {}",
+                name,
+                statement.parameter_count,
+                arg_count
+            )
+            .into(),
+        ));
     }
 
-    final_result
+    params.sql = Some(statement.sql);
+    params.default_schema = statement.default_schema;
+    params.prepared = Some(statement.name);
+    params.query_type.get_or_insert(Command::Arrow);
+
+    with_db_retry(state, &params, |state, params| Box::pin(handle(state, params))).await
+}
+
+#[derive(Serialize)]
+struct PoolStatusResponse {
+    id: String,
+    db_path: String,
+    pool_size: usize,
+    access_mode: String,
+    in_use: usize,
+    idle: usize,
+    total: usize,
+    timeout: Duration,
 }
 
+#[derive(Serialize)]
+struct PoolStatusError {
+    id: String,
+    error: String,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum PoolStatusResult {
+    Success(PoolStatusResponse),
+    Error(PoolStatusError),
+}
+
+#[derive(Serialize)]
+struct QueryStatus {
+    id: String,
+    database: String,
+    sql: String,
+    started_at: String,
+}
+
+#[derive(Serialize)]
+pub struct StatusResponse {
+    pools: Vec<PoolStatusResult>,
+    total_pools: usize,
+    running_queries: Vec<QueryStatus>,
+    total_running_queries: usize,
+}
+
+pub async fn status(state: &AppState) -> Result<StatusResponse, AppError> {
+    let states = state.states.lock().await;
+    let mut pool_statuses = Vec::new();
+
+    for (id, db_state) in states.iter() {
+        match db_state.db.status() {
+            Ok(pool_status) => {
+                pool_statuses.push(PoolStatusResult::Success(PoolStatusResponse {
+                    id: id.clone(),
+                    db_path: pool_status.db_path,
+                    pool_size: pool_status.pool_size,
+                    access_mode: pool_status.access_mode,
+                    in_use: pool_status.in_use,
+                    idle: pool_status.idle,
+                    total: pool_status.total,
+                    timeout: pool_status.timeout,
+                }));
+            }
+            Err(error) => {
+                pool_statuses.push(PoolStatusResult::Error(PoolStatusError {
+                    id: id.clone(),
+                    error: error.to_string(),
+                }));
+            }
+        }
+    }
+
+    let running_queries = state.get_running_queries().await;
+    let query_statuses: Vec<QueryStatus> = running_queries
+        .into_iter()
+        .map(|query| QueryStatus {
+            id: query.id,
+            database: query.database,
+            sql: query.sql,
+            started_at: query.started_at
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+                .to_string(),
+        })
+        .collect();
+
+    let total_running_queries = query_statuses.len();
+
+    Ok(StatusResponse {
+        pools: pool_statuses,
+        total_pools: states.len(),
+        running_queries: query_statuses,
+        total_running_queries,
+    })
+}
 
 pub async fn cancel_query(state: &AppState, query_id: String) -> Result<QueryResponse, AppError> {
     let cancelled = state.cancel_query(&query_id).await?;
@@ -289,3 +1188,95 @@ pub async fn killall_queries_for_database(state: &AppState, database: String) ->
 
     Ok(QueryResponse::Json(response.to_string()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, Int32Array};
+    use crate::interfaces::{JsonFormat, TimestampFormat};
+    use arrow::datatypes::{DataType, Field, Schema};
+
+    fn batch(values: Vec<i32>) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)]));
+        RecordBatch::try_new(schema, vec![Arc::new(Int32Array::from(values))]).unwrap()
+    }
+
+    #[test]
+    fn test_take_rows() {
+        let batches = vec![batch(vec![1, 2, 3]), batch(vec![4, 5]), batch(vec![6])];
+
+        let taken = take_rows(&batches, 4);
+        let lengths: Vec<usize> = taken.iter().map(RecordBatch::num_rows).collect();
+        assert_eq!(lengths, vec![3, 1, 0]);
+        assert_eq!(taken[1].column(0).as_any().downcast_ref::<Int32Array>().unwrap().value(0), 4);
+    }
+
+    #[test]
+    fn test_take_rows_beyond_result() {
+        let batches = vec![batch(vec![1, 2]), batch(vec![3])];
+
+        let rows: usize = take_rows(&batches, 10).iter().map(RecordBatch::num_rows).sum();
+        assert_eq!(rows, 3);
+        assert!(take_rows(&batches, 0).iter().all(|batch| batch.num_rows() == 0));
+    }
+
+    #[test]
+    fn test_result_cache_key_arrow_format() {
+        let sql = "SELECT 1";
+        let stream = result_cache_key(sql, &QueryParams::default(), &Command::Arrow);
+        let file = result_cache_key(
+            sql,
+            &QueryParams { arrow_format: Some(ArrowFormat::File), ..Default::default() },
+            &Command::Arrow,
+        );
+        let zstd = result_cache_key(
+            sql,
+            &QueryParams { compression: Some(ArrowCompression::Zstd), ..Default::default() },
+            &Command::Arrow,
+        );
+        let lz4 = result_cache_key(
+            sql,
+            &QueryParams { compression: Some(ArrowCompression::Lz4Frame), ..Default::default() },
+            &Command::Arrow,
+        );
+
+        assert_ne!(stream, file);
+        assert_ne!(stream, zstd);
+        assert_ne!(zstd, lz4);
+        assert_eq!(
+            stream,
+            result_cache_key(
+                sql,
+                &QueryParams { arrow_format: Some(ArrowFormat::Stream), ..Default::default() },
+                &Command::Arrow
+            )
+        );
+    }
+
+    #[test]
+    fn test_result_cache_key_json_options() {
+        let sql = "SELECT 1";
+        let rows = result_cache_key(sql, &QueryParams::default(), &Command::Json);
+        let arrays = result_cache_key(
+            sql,
+            &QueryParams { json_format: Some(JsonFormat::Arrays), ..Default::default() },
+            &Command::Json,
+        );
+        let strings = result_cache_key(
+            sql,
+            &QueryParams { big_numbers_as_strings: Some(true), ..Default::default() },
+            &Command::Json,
+        );
+        let epoch = result_cache_key(
+            sql,
+            &QueryParams { timestamp_format: Some(TimestampFormat::EpochMs), ..Default::default() },
+            &Command::Json,
+        );
+
+        assert_ne!(rows, arrays);
+        assert_ne!(rows, strings);
+        assert_ne!(rows, epoch);
+        assert_ne!(arrays, epoch);
+        assert_ne!(rows, result_cache_key(sql, &QueryParams::default(), &Command::Arrow));
+    }
+}
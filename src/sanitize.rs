@@ -415,10 +415,15 @@ mod tests {
                 pool_timeout: 30,
                 pool_idle_timeout: 0,
                 pool_max_lifetime: 0,
+                async_result_retention: 3600,
             },
             root: "/tmp".to_string(),
             states: Mutex::new(HashMap::new()),
             running_queries: Mutex::new(HashMap::new()),
+            flight_tickets: Mutex::new(HashMap::new()),
+            async_queries: Mutex::new(HashMap::new()),
+            cursors: Mutex::new(HashMap::new()),
+            sessions: Mutex::new(HashMap::new()),
         });
 
         let router = app(app_state, 30, None).await.unwrap();
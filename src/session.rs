@@ -0,0 +1,198 @@
+use arrow::{datatypes::SchemaRef, record_batch::RecordBatch};
+use duckdb::{Connection, params_from_iter};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+use crate::constants::SESSION_IDLE_TIMEOUT;
+use crate::db::monitoring::catch_query_panic;
+use crate::db::{SchemaGuard, TimeZoneGuard, inline_args, statement_args};
+use crate::interfaces::{AppError, Command, QueryParams, QueryResponse, SessionParams};
+use crate::query::{self, RunningQueryGuard};
+use crate::sql::{enforce_query_limit, is_writable_sql};
+use crate::state::{AppState, Session};
+
+#[derive(Serialize)]
+pub struct SessionInfo {
+    session_id: String,
+    database: String,
+    idle_timeout: u64,
+}
+
+/// Opens a session on a connection of its own, outside the database's pool.
+pub async fn open(state: &AppState, params: SessionParams) -> Result<SessionInfo, AppError> {
+    if params.create.unwrap_or(false) {
+        state.create_database_if_not_exists(&params.database).await?;
+    }
+
+    let db_state = state
+        .get_or_create_db_state(&params.database, &None, &None, &None)
+        .await?;
+    let connection = tokio::task::spawn_blocking(move || db_state.db.connect()).await??;
+
+    let idle_timeout = params
+        .idle_timeout
+        .map(Duration::from_secs)
+        .unwrap_or(SESSION_IDLE_TIMEOUT);
+    let session = Session {
+        id: Uuid::new_v4().to_string(),
+        database: params.database,
+        connection: Arc::new(std::sync::Mutex::new(connection)),
+        idle_timeout,
+        last_used: SystemTime::now(),
+    };
+
+    tracing::info!("Opened session {} for database {}", session.id, session.database);
+
+    let info = SessionInfo {
+        session_id: session.id.clone(),
+        database: session.database.clone(),
+        idle_timeout: idle_timeout.as_secs(),
+    };
+    state.open_session(session).await;
+
+    Ok(info)
+}
+
+/// Closes a session. An open transaction is rolled back once its running queries finish.
+pub async fn close(state: &AppState, session_id: &str) -> Result<QueryResponse, AppError> {
+    match state.close_session(session_id).await {
+        Some(session) => {
+            tracing::info!("Closed session {} for database {}", session.id, session.database);
+            Ok(QueryResponse::Empty)
+        }
+        None => Err(AppError::BadRequest(anyhow::anyhow!("Session not found: {}", session_id).into())),
+    }
+}
+
+/// Runs a query on the session's connection. Queries of one session run one at a time and
+/// their results are not cached, since they may read the session's temp tables.
+pub async fn handle(state: &Arc<AppState>, session_id: &str, params: &QueryParams) -> Result<QueryResponse, AppError> {
+    let session = state.get_session(session_id).await.ok_or_else(|| {
+        AppError::BadRequest(anyhow::anyhow!("Session not found or expired: {}", session_id).into())
+    })?;
+
+    if session.database != params.database {
+        return Err(AppError::BadRequest(
+            anyhow::anyhow!("Session {} is connected to database {}", session_id, session.database).into(),
+        ));
+    }
+
+    let command = match &params.query_type {
+        Some(command @ (Command::Arrow | Command::Json | Command::Csv | Command::Ndjson | Command::Parquet | Command::Exec)) => {
+            command.clone()
+        }
+        Some(command) => {
+            return Err(AppError::BadRequest(
+                anyhow::anyhow!("{:?} queries cannot run in a session", command).into(),
+            ));
+        }
+        None => return Err(AppError::BadRequest(anyhow::anyhow!("Query type is required").into())),
+    };
+
+    let sql = params
+        .sql
+        .clone()
+        .filter(|sql| !sql.trim().is_empty())
+        .ok_or_else(|| AppError::BadRequest(anyhow::anyhow!("SQL query is required").into()))?;
+
+    query::check_args(params)?;
+
+    let (query_id, cancel_token) = query::register_query(state, params, &sql).await?;
+    let _guard = RunningQueryGuard {
+        state: Arc::clone(state),
+        query_id: query_id.clone(),
+        cancel_token: cancel_token.clone(),
+    };
+
+    tracing::info!(
+        "Command: '{:?}', Query ID: '{}', Session: '{}', Params: '{:?}'",
+        command,
+        query_id,
+        session_id,
+        params
+    );
+
+    let limit = params.limit.unwrap_or(state.defaults.row_limit);
+    let writable = is_writable_sql(&sql);
+    let runtime = tokio::runtime::Handle::current();
+    let result = tokio::task::spawn_blocking({
+        let params = params.clone();
+        let command = command.clone();
+        move || {
+            let conn = session
+                .connection
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Session connection is unusable after a panic"))?;
+
+            // Interrupt DuckDB when the query is cancelled
+            let interrupt = conn.interrupt_handle();
+            let watcher = runtime.spawn(async move {
+                cancel_token.cancelled().await;
+                interrupt.interrupt();
+            });
+
+            let result = catch_query_panic(&sql, || run_statement(&conn, &command, &params, &sql, limit));
+
+            watcher.abort();
+            result
+        }
+    })
+    .await??;
+
+    // The session shares the database instance with the pool, so pooled connections see its
+    // writes right away, but cached results are outdated
+    if writable {
+        let db_state = state.get_or_create_db_state(&params.database, &None, &None, &None).await?;
+        db_state.cache.lock().await.clear();
+    }
+
+    let response = match result {
+        Some((schema, batches)) => {
+            let body = query::encode_batches(&command, params, &schema, &batches)?;
+            query::buffered_response(&command, params, body)?
+        }
+        None => QueryResponse::Empty,
+    };
+
+    Ok(QueryResponse::QueryWithId {
+        query_id,
+        result: Box::new(response),
+    })
+}
+
+fn run_statement(
+    conn: &Connection,
+    command: &Command,
+    params: &QueryParams,
+    sql: &str,
+    limit: usize,
+) -> anyhow::Result<Option<(SchemaRef, Vec<RecordBatch>)>> {
+    let _schema = SchemaGuard::set(conn, params.default_schema.as_deref())?;
+
+    if let Some(prepare_sql) = &params.prepare_sql {
+        conn.execute_batch(prepare_sql)?;
+    }
+
+    let _timezone = TimeZoneGuard::set(conn, params.timezone.as_deref())?;
+
+    if let Command::Exec = command {
+        conn.execute_batch(sql)?;
+        return Ok(None);
+    }
+
+    let (sql, args, named_args) = inline_args(
+        sql,
+        params.args.as_deref().unwrap_or_default(),
+        &params.named_args.clone().unwrap_or_default(),
+    )?;
+    let effective_sql = enforce_query_limit(&sql, limit)?;
+
+    let mut stmt = conn.prepare(&effective_sql)?;
+    let args = statement_args(&stmt, &args, &named_args)?;
+    let arrow = stmt.query_arrow(params_from_iter(args.iter()))?;
+    let schema = arrow.get_schema();
+
+    Ok(Some((schema, arrow.collect())))
+}
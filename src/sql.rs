@@ -2,7 +2,9 @@ use sqlparser::{
     ast::{Expr, LimitClause, Statement, Value},
     dialect::DuckDbDialect,
     parser::Parser,
+    tokenizer::{Location, Token, Tokenizer},
 };
+use std::collections::BTreeMap;
 use tracing::log::{info, warn};
 
 pub fn enforce_query_limit(sql: &str, limit: usize) -> anyhow::Result<String> {
@@ -40,6 +42,142 @@ pub fn enforce_query_limit(sql: &str, limit: usize) -> anyhow::Result<String> {
     }
 }
 
+/// Whether [`enforce_query_limit`] adds a LIMIT to `sql`, i.e. it has a query without one.
+pub fn needs_query_limit(sql: &str) -> bool {
+    let dialect = DuckDbDialect {};
+    match Parser::parse_sql(&dialect, sql) {
+        Ok(statements) => statements
+            .iter()
+            .any(|stmt| matches!(stmt, Statement::Query(query) if query.limit_clause.is_none())),
+        Err(_) => false,
+    }
+}
+
+pub fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+pub fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Writes SQL text into the placeholders of `sql`: `positional` by the zero-based index of a
+/// `?` or `$1` placeholder and `named` by the name of a `$name` placeholder. The remaining
+/// numbered placeholders are renumbered to follow each other again.
+pub fn replace_placeholders(
+    sql: &str,
+    positional: &BTreeMap<usize, String>,
+    named: &BTreeMap<String, String>,
+) -> anyhow::Result<String> {
+    let dialect = DuckDbDialect {};
+    let tokens = Tokenizer::new(&dialect, sql).tokenize_with_location()?;
+
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(sql.match_indices('\n').map(|(index, _)| index + 1))
+        .collect();
+    // Locations count characters from 1 within a line
+    let offset = |location: Location| {
+        let line_start = line_starts[location.line as usize - 1];
+        sql[line_start..]
+            .char_indices()
+            .nth(location.column as usize - 1)
+            .map_or(sql.len(), |(index, _)| line_start + index)
+    };
+
+    let mut replaced = String::with_capacity(sql.len());
+    let mut copied = 0;
+    let mut question_marks = 0;
+
+    for token in tokens {
+        let Token::Placeholder(placeholder) = &token.token else {
+            continue;
+        };
+
+        let (prefix, name) = placeholder.split_at(1);
+        let replacement = if let Some(index) = (name.is_empty() && prefix == "?").then_some(question_marks) {
+            question_marks += 1;
+            match positional.get(&index) {
+                Some(text) => text.clone(),
+                None => continue,
+            }
+        }
+        else if let Ok(number) = name.parse::<usize>() {
+            let index = number.saturating_sub(1);
+            match positional.get(&index) {
+                Some(text) => text.clone(),
+                None => format!("{}{}", prefix, number - positional.range(..index).count()),
+            }
+        }
+        else {
+            match named.get(name) {
+                Some(text) => text.clone(),
+                None => continue,
+            }
+        };
+
+        let start = offset(token.span.start);
+        replaced.push_str(&sql[copied..start]);
+        replaced.push_str(&replacement);
+        copied = offset(token.span.end);
+    }
+
+    replaced.push_str(&sql[copied..]);
+    Ok(replaced)
+}
+
+pub fn schema_probe_sql(sql: &str) -> Option<String> {
+    let dialect = DuckDbDialect {};
+    match Parser::parse_sql(&dialect, sql) {
+        Ok(statements) => match statements.as_slice() {
+            [Statement::Query(query)] => Some(format!("SELECT * FROM ({}) LIMIT 0", query)),
+            _ => None,
+        },
+        Err(e) => {
+            warn!("Skipping schema probe due to SQL parse error: {e}. Query: {sql}");
+            None
+        }
+    }
+}
+
+/// Whether `sql` is a single query, or `None` when it does not parse.
+pub fn is_single_query(sql: &str) -> Option<bool> {
+    let dialect = DuckDbDialect {};
+    match Parser::parse_sql(&dialect, sql) {
+        Ok(statements) => Some(matches!(statements.as_slice(), [Statement::Query(_)])),
+        Err(_) => None,
+    }
+}
+
+/// Whether `sql` parses as exactly one statement.
+pub fn is_single_statement(sql: &str) -> bool {
+    let dialect = DuckDbDialect {};
+    Parser::parse_sql(&dialect, sql).is_ok_and(|statements| statements.len() == 1)
+}
+
+/// Whether `sql` is a single query that sorts its result.
+pub fn has_order_by(sql: &str) -> bool {
+    let dialect = DuckDbDialect {};
+    match Parser::parse_sql(&dialect, sql) {
+        Ok(statements) => matches!(statements.as_slice(), [Statement::Query(query)] if query.order_by.is_some()),
+        Err(_) => false,
+    }
+}
+
+/// Wraps a single query so that it returns `limit` rows starting at `offset`.
+pub fn page_sql(sql: &str, limit: usize, offset: usize) -> Option<String> {
+    let dialect = DuckDbDialect {};
+    match Parser::parse_sql(&dialect, sql) {
+        Ok(statements) => match statements.as_slice() {
+            [Statement::Query(query)] => Some(format!("SELECT * FROM ({}) LIMIT {} OFFSET {}", query, limit, offset)),
+            _ => None,
+        },
+        Err(e) => {
+            warn!("Cannot page query due to SQL parse error: {e}. Query: {sql}");
+            None
+        }
+    }
+}
+
 pub fn is_writable_sql(sql: &str) -> bool {
     let dialect = DuckDbDialect {};
     match Parser::parse_sql(&dialect, sql) {
@@ -71,3 +209,96 @@ pub fn is_writable_sql(sql: &str) -> bool {
         Err(_) => false,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enforce_query_limit() {
+        let limited = enforce_query_limit("select a from t", 10).unwrap();
+        assert_eq!(limited, "SELECT a FROM t LIMIT 10");
+
+        let limited = enforce_query_limit("select a from t limit 100", 10).unwrap();
+        assert_eq!(limited, "SELECT a FROM t LIMIT 100");
+    }
+
+    #[test]
+    fn test_needs_query_limit() {
+        assert!(needs_query_limit("select a from t"));
+        assert!(!needs_query_limit("select a from t limit 100"));
+        assert!(!needs_query_limit("create table t (a int)"));
+        assert!(!needs_query_limit("not sql at all"));
+    }
+
+    #[test]
+    fn test_page_sql() {
+        let paged = page_sql("select a from t order by a", 11, 20);
+        assert_eq!(paged.as_deref(), Some("SELECT * FROM (SELECT a FROM t ORDER BY a) LIMIT 11 OFFSET 20"));
+    }
+
+    #[test]
+    fn test_page_sql_needs_single_query() {
+        assert!(page_sql("select 1; select 2", 10, 0).is_none());
+        assert!(page_sql("create table t (a int)", 10, 0).is_none());
+        assert!(page_sql("not sql at all", 10, 0).is_none());
+    }
+
+    #[test]
+    fn test_is_single_statement() {
+        assert!(is_single_statement("insert into t values (?, ?)"));
+        assert!(!is_single_statement("insert into t values (1); select 1"));
+        assert!(!is_single_statement("not sql at all"));
+    }
+
+    #[test]
+    fn test_has_order_by() {
+        assert!(has_order_by("select a from t order by a"));
+        assert!(!has_order_by("select a from t"));
+        assert!(!has_order_by("select a from (select a from t order by a)"));
+        assert!(!has_order_by("select a from t order by a; select 1"));
+    }
+
+    #[test]
+    fn test_quote_identifier() {
+        assert_eq!(quote_identifier("orders"), r#""orders""#);
+        assert_eq!(quote_identifier(r#"my "table""#), r#""my ""table""""#);
+    }
+
+    #[test]
+    fn test_schema_probe_sql() {
+        let probe = schema_probe_sql("select a from t where a > 1");
+        assert_eq!(probe.as_deref(), Some("SELECT * FROM (SELECT a FROM t WHERE a > 1) LIMIT 0"));
+
+        assert!(schema_probe_sql("select 1; select 2").is_none());
+        assert!(schema_probe_sql("insert into t values (1)").is_none());
+        assert!(schema_probe_sql("not sql at all").is_none());
+    }
+
+    #[test]
+    fn test_quote_literal() {
+        assert_eq!(quote_literal("Europe/Berlin"), "'Europe/Berlin'");
+        assert_eq!(quote_literal("it's"), "'it''s'");
+    }
+
+    #[test]
+    fn test_replace_placeholders() {
+        let positional = BTreeMap::from([(1, "[1, 2]".to_string())]);
+        let named = BTreeMap::from([("day".to_string(), "CAST('2024-01-31' AS DATE)".to_string())]);
+
+        let sql = "SELECT ?, '?' AS q, a IN ? -- ?\nFROM t WHERE b = ?";
+        assert_eq!(
+            replace_placeholders(sql, &positional, &BTreeMap::new()).unwrap(),
+            "SELECT ?, '?' AS q, a IN [1, 2] -- ?\nFROM t WHERE b = ?"
+        );
+
+        let sql = "SELECT $1, $2, $3, $1";
+        assert_eq!(replace_placeholders(sql, &positional, &BTreeMap::new()).unwrap(), "SELECT $1, [1, 2], $2, $1");
+
+        let sql = "SELECT 'ä' AS x FROM t WHERE d = $day AND e = $other";
+        assert_eq!(
+            replace_placeholders(sql, &BTreeMap::new(), &named).unwrap(),
+            "SELECT 'ä' AS x FROM t WHERE d = CAST('2024-01-31' AS DATE) AND e = $other"
+        );
+    }
+}
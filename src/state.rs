@@ -1,16 +1,17 @@
 use anyhow::Result;
+use arrow::{datatypes::SchemaRef, record_batch::RecordBatch};
 use duckdb::AccessMode;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use tokio::sync::Mutex;
 use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
-use crate::constants::MEMORY_DB_PATH;
+use crate::constants::{CURSOR_IDLE_TIMEOUT, FLIGHT_TICKET_TTL, MEMORY_DB_PATH, STATE_SWEEP_INTERVAL};
 use crate::db::ConnectionPool;
-use crate::interfaces::{AppError, DbDefaults, DbState, DbType, DucklakeConfig, Extension, SecretConfig};
+use crate::interfaces::{AppError, DbDefaults, DbState, DbType, DucklakeConfig, Extension, QueryParams, SecretConfig};
 
 #[derive(Clone)]
 pub struct RunningQuery {
@@ -21,11 +22,88 @@ pub struct RunningQuery {
     pub started_at: std::time::SystemTime,
 }
 
+/// Ticket handed out by the Flight server in place of the query it stands for.
+#[derive(Clone)]
+pub struct FlightTicket {
+    pub id: String,
+    pub params: QueryParams,
+    pub expires_at: SystemTime,
+    pub status: FlightTicketStatus,
+}
+
+#[derive(Clone)]
+pub enum FlightTicketStatus {
+    /// Issued by `get_flight_info`; the query runs when the ticket is fetched.
+    Deferred,
+    /// Issued by `poll_flight_info`; the query runs in the background.
+    Running { query_id: String },
+    Ready { schema: SchemaRef, batches: Arc<Vec<RecordBatch>> },
+    Failed(String),
+}
+
+/// A query submitted with `async=true`. It stays registered until its result expires.
+#[derive(Clone)]
+pub struct AsyncQuery {
+    pub id: String,
+    pub params: QueryParams,
+    pub submitted_at: SystemTime,
+    pub finished_at: Option<SystemTime>,
+    /// Rows produced so far.
+    pub rows: usize,
+    pub status: AsyncQueryStatus,
+}
+
+#[derive(Clone)]
+pub enum AsyncQueryStatus {
+    /// Waiting for the background task to pick the query up.
+    Queued,
+    Running,
+    Done { schema: SchemaRef, batches: Arc<Vec<RecordBatch>> },
+    Failed(String),
+    Cancelled,
+}
+
+impl AsyncQueryStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AsyncQueryStatus::Queued => "queued",
+            AsyncQueryStatus::Running => "running",
+            AsyncQueryStatus::Done { .. } => "done",
+            AsyncQueryStatus::Failed(_) => "failed",
+            AsyncQueryStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// Where a client paging through a result is. Each page re-runs the query from `offset`.
+#[derive(Clone)]
+pub struct Cursor {
+    pub id: String,
+    pub params: QueryParams,
+    pub offset: usize,
+    pub last_used: SystemTime,
+}
+
+/// A connection pinned for a client across requests, so transactions and temp tables
+/// survive between queries. It is closed once it has been idle for `idle_timeout`.
+#[derive(Clone)]
+pub struct Session {
+    pub id: String,
+    pub database: String,
+    pub connection: Arc<std::sync::Mutex<duckdb::Connection>>,
+    pub idle_timeout: Duration,
+    pub last_used: SystemTime,
+}
+
 pub struct AppState {
     pub defaults: DbDefaults,
     pub root: String,
     pub states: Mutex<HashMap<String, Arc<DbState>>>,
     pub running_queries: Mutex<HashMap<String, RunningQuery>>,
+    pub flight_tickets: Mutex<HashMap<String, FlightTicket>>,
+    pub async_queries: Mutex<HashMap<String, AsyncQuery>>,
+    pub cursors: Mutex<HashMap<String, Cursor>>,
+    pub sessions: Mutex<HashMap<String, Session>>,
 }
 
 impl AppState {
@@ -157,6 +235,37 @@ impl AppState {
         (query_id, cancel_token)
     }
 
+    /// Starts a query under an ID the client picked, so it can cancel the query or match
+    /// up its result before the server answers.
+    pub async fn start_query_with_id(
+        &self,
+        query_id: String,
+        database: String,
+        sql: String,
+    ) -> Result<CancellationToken, AppError> {
+        let mut queries = self.running_queries.lock().await;
+
+        // A cancelled entry belongs to a finished query whose cleanup has not run yet
+        if queries.get(&query_id).is_some_and(|query| !query.cancel_token.is_cancelled()) {
+            return Err(AppError::BadRequest(
+                anyhow::anyhow!("Query {} is already running", query_id).into(),
+            ));
+        }
+
+        let cancel_token = CancellationToken::new();
+        let running_query = RunningQuery {
+            id: query_id.clone(),
+            cancel_token: cancel_token.clone(),
+            database: database.clone(),
+            sql,
+            started_at: std::time::SystemTime::now(),
+        };
+        queries.insert(query_id.clone(), running_query);
+
+        tracing::info!("Started query {} for database {}", query_id, database);
+        Ok(cancel_token)
+    }
+
     pub async fn cancel_query(&self, query_id: &str) -> Result<bool, AppError> {
         let mut queries = self.running_queries.lock().await;
 
@@ -174,6 +283,174 @@ impl AppState {
         self.running_queries.lock().await.values().cloned().collect()
     }
 
+    pub async fn issue_flight_ticket(&self, params: QueryParams, status: FlightTicketStatus) -> FlightTicket {
+        let ticket = FlightTicket {
+            id: Uuid::new_v4().to_string(),
+            params,
+            expires_at: SystemTime::now() + FLIGHT_TICKET_TTL,
+            status,
+        };
+
+        self.remove_expired_flight_tickets().await;
+        self.flight_tickets
+            .lock()
+            .await
+            .insert(ticket.id.clone(), ticket.clone());
+
+        ticket
+    }
+
+    pub async fn get_flight_ticket(&self, id: &str) -> Option<FlightTicket> {
+        self.remove_expired_flight_tickets().await;
+        self.flight_tickets.lock().await.get(id).cloned()
+    }
+
+    pub async fn set_flight_ticket_status(&self, id: &str, status: FlightTicketStatus) {
+        if let Some(ticket) = self.flight_tickets.lock().await.get_mut(id) {
+            ticket.status = status;
+        }
+    }
+
+    /// Drops expired server-side state every `STATE_SWEEP_INTERVAL`, so results nobody comes
+    /// back for and the connections of idle sessions are freed without waiting for the next lookup.
+    pub async fn sweep_expired(&self, cancel_token: CancellationToken) {
+        let mut ticker = tokio::time::interval(STATE_SWEEP_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = cancel_token.cancelled() => return,
+                _ = ticker.tick() => {}
+            }
+
+            self.remove_expired_flight_tickets().await;
+            self.remove_idle_sessions().await;
+        }
+    }
+
+    async fn remove_expired_flight_tickets(&self) {
+        let now = SystemTime::now();
+        let expired: Vec<FlightTicket> = {
+            let mut tickets = self.flight_tickets.lock().await;
+            let ids: Vec<String> = tickets
+                .values()
+                .filter(|ticket| ticket.expires_at <= now)
+                .map(|ticket| ticket.id.clone())
+                .collect();
+            ids.iter().filter_map(|id| tickets.remove(id)).collect()
+        };
+
+        for ticket in expired {
+            // Nobody can fetch the result anymore, so stop computing it
+            if let FlightTicketStatus::Running { query_id } = &ticket.status {
+                let _ = self.cancel_query(query_id).await;
+            }
+            tracing::debug!("Flight ticket {} expired", ticket.id);
+        }
+    }
+
+    pub async fn submit_async_query(&self, query: AsyncQuery) {
+        self.remove_expired_async_queries().await;
+        self.async_queries.lock().await.insert(query.id.clone(), query);
+    }
+
+    pub async fn get_async_query(&self, id: &str) -> Option<AsyncQuery> {
+        self.remove_expired_async_queries().await;
+        self.async_queries.lock().await.get(id).cloned()
+    }
+
+    pub async fn update_async_query(&self, id: &str, update: impl FnOnce(&mut AsyncQuery)) {
+        if let Some(query) = self.async_queries.lock().await.get_mut(id) {
+            update(query);
+        }
+    }
+
+    /// Drops finished async queries whose result has been kept for the retention period.
+    async fn remove_expired_async_queries(&self) {
+        let retention = Duration::from_secs(self.defaults.async_result_retention);
+        let now = SystemTime::now();
+
+        self.async_queries.lock().await.retain(|id, query| {
+            let expired = query
+                .finished_at
+                .is_some_and(|finished_at| finished_at + retention <= now);
+            if expired {
+                tracing::debug!("Result of async query {} expired", id);
+            }
+            !expired
+        });
+    }
+
+    pub async fn open_cursor(&self, params: QueryParams) -> Cursor {
+        let cursor = Cursor {
+            id: Uuid::new_v4().to_string(),
+            params,
+            offset: 0,
+            last_used: SystemTime::now(),
+        };
+
+        self.remove_idle_cursors().await;
+        self.cursors.lock().await.insert(cursor.id.clone(), cursor.clone());
+
+        cursor
+    }
+
+    pub async fn get_cursor(&self, id: &str) -> Option<Cursor> {
+        self.remove_idle_cursors().await;
+        self.cursors.lock().await.get(id).cloned()
+    }
+
+    pub async fn advance_cursor(&self, id: &str, offset: usize) {
+        if let Some(cursor) = self.cursors.lock().await.get_mut(id) {
+            cursor.offset = offset;
+            cursor.last_used = SystemTime::now();
+        }
+    }
+
+    pub async fn close_cursor(&self, id: &str) {
+        self.cursors.lock().await.remove(id);
+    }
+
+    async fn remove_idle_cursors(&self) {
+        let now = SystemTime::now();
+        self.cursors.lock().await.retain(|id, cursor| {
+            let idle = cursor.last_used + CURSOR_IDLE_TIMEOUT <= now;
+            if idle {
+                tracing::debug!("Cursor {} expired", id);
+            }
+            !idle
+        });
+    }
+
+    pub async fn open_session(&self, session: Session) {
+        self.remove_idle_sessions().await;
+        self.sessions.lock().await.insert(session.id.clone(), session);
+    }
+
+    /// Looks up a session and counts the lookup as use.
+    pub async fn get_session(&self, id: &str) -> Option<Session> {
+        self.remove_idle_sessions().await;
+        self.sessions.lock().await.get_mut(id).map(|session| {
+            session.last_used = SystemTime::now();
+            session.clone()
+        })
+    }
+
+    pub async fn close_session(&self, id: &str) -> Option<Session> {
+        self.remove_idle_sessions().await;
+        self.sessions.lock().await.remove(id)
+    }
+
+    async fn remove_idle_sessions(&self) {
+        let now = SystemTime::now();
+        self.sessions.lock().await.retain(|id, session| {
+            let idle = session.last_used + session.idle_timeout <= now;
+            if idle {
+                tracing::info!("Closing idle session {} for database {}", id, session.database);
+            }
+            !idle
+        });
+    }
+
     pub async fn create_database_if_not_exists(&self, database: &str) -> Result<(), AppError> {
         if database.trim().starts_with(MEMORY_DB_PATH) {
             return Ok(());
@@ -0,0 +1,261 @@
+use arrow::{array::AsArray, datatypes::SchemaRef, record_batch::RecordBatch};
+use arrow_ipc::reader::{FileReader, StreamReader};
+use axum::{
+    body::Bytes,
+    extract::{FromRequest, Multipart, Request},
+    http::header::CONTENT_TYPE,
+};
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+
+use crate::constants::{METADATA_ROW_LIMIT, RECORD_BATCH_CHANNEL_CAPACITY, UPLOAD_DIRECTORY};
+use crate::interfaces::{AppError, IngestMode, SqlValue, UploadFormat, UploadParams};
+use crate::query::RunningQueryGuard;
+use crate::sql::quote_literal;
+use crate::state::AppState;
+
+/// Leading bytes of an Arrow IPC file; a stream starts right away with its schema message.
+const ARROW_FILE_MAGIC: &[u8] = b"ARROW1";
+
+#[derive(Serialize)]
+pub struct UploadColumn {
+    name: String,
+    #[serde(rename = "type")]
+    data_type: String,
+}
+
+#[derive(Serialize)]
+pub struct UploadInfo {
+    database: String,
+    schema: String,
+    table: String,
+    mode: IngestMode,
+    format: UploadFormat,
+    rows: usize,
+    /// The table's columns after the upload, as DuckDB inferred them for a new table.
+    columns: Vec<UploadColumn>,
+}
+
+/// An uploaded file under the database root, removed once it is loaded.
+struct UploadFile(PathBuf);
+
+impl Drop for UploadFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.0) {
+            tracing::warn!("Failed to remove upload {}: {}", self.0.display(), e);
+        }
+    }
+}
+
+/// Writes an upload into a table. The body is either the file itself or a multipart form whose
+/// first file field holds it. It is streamed to a file under `<root>/.uploads` and read from
+/// there with DuckDB's `read_csv`, `read_parquet` or `read_json`; Arrow IPC goes through the
+/// Arrow appender instead. The upload is listed with the running queries while it lasts.
+pub async fn upload(
+    state: &Arc<AppState>,
+    database: String,
+    table: String,
+    params: UploadParams,
+    request: Request,
+) -> Result<UploadInfo, AppError> {
+    let schema = params.schema.unwrap_or_else(|| "main".to_string());
+    let mode = params.mode.unwrap_or_default();
+
+    let content_type = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+
+    let directory = Path::new(&state.root).join(UPLOAD_DIRECTORY);
+    tokio::fs::create_dir_all(&directory).await?;
+    let file = UploadFile(directory.join(uuid::Uuid::new_v4().to_string()));
+
+    let detected = if content_type.starts_with("multipart/form-data") {
+        let mut multipart = Multipart::from_request(request, &())
+            .await
+            .map_err(|e| AppError::BadRequest(anyhow::anyhow!("{}", e).into()))?;
+
+        let field = loop {
+            match multipart
+                .next_field()
+                .await
+                .map_err(|e| AppError::BadRequest(anyhow::anyhow!("{}", e).into()))?
+            {
+                Some(field) if field.file_name().is_some() => break field,
+                Some(_) => continue,
+                None => {
+                    return Err(AppError::BadRequest(anyhow::anyhow!("The form holds no file").into()));
+                }
+            }
+        };
+
+        let detected = field
+            .file_name()
+            .and_then(UploadFormat::detect)
+            .or_else(|| field.content_type().and_then(UploadFormat::detect));
+        write_file(&file.0, field).await?;
+        detected
+    }
+    else {
+        write_file(&file.0, request.into_body().into_data_stream()).await?;
+        UploadFormat::detect(&content_type)
+    };
+
+    let format = params.format.or(detected).ok_or_else(|| {
+        AppError::BadRequest(
+            anyhow::anyhow!("Upload format is required: csv, parquet, json or arrow").into(),
+        )
+    })?;
+
+    let db_state = state.get_or_create_db_state(&database, &None, &None, &None).await?;
+
+    let (query_id, cancel_token) = state
+        .start_query(database.clone(), format!("UPLOAD INTO {}.{}", schema, table))
+        .await;
+    let _guard = RunningQueryGuard {
+        state: Arc::clone(state),
+        query_id,
+        cancel_token: cancel_token.clone(),
+    };
+
+    let path = quote_literal(&file.0.to_string_lossy());
+    let rows = match format {
+        UploadFormat::Csv => db_state.db.load_table(&schema, &table, mode, &format!("read_csv({})", path)).await?,
+        UploadFormat::Parquet => {
+            db_state.db.load_table(&schema, &table, mode, &format!("read_parquet({})", path)).await?
+        }
+        UploadFormat::Json => db_state.db.load_table(&schema, &table, mode, &format!("read_json({})", path)).await?,
+        UploadFormat::Arrow => {
+            let path = file.0.clone();
+            let (schema_ref, batches) = tokio::task::spawn_blocking(move || open_arrow_file(&path))
+                .await
+                .map_err(|e| anyhow::anyhow!("Task error: {}", e))?
+                .map_err(|e| AppError::BadRequest(anyhow::anyhow!("Failed to read Arrow upload: {}", e).into()))?;
+
+            let (tx, rx) = mpsc::channel(RECORD_BATCH_CHANNEL_CAPACITY);
+            let reader = tokio::task::spawn_blocking(move || forward_arrow_batches(schema_ref, batches, tx));
+
+            let (rows, read) = tokio::join!(db_state.db.append_record_batches(&schema, &table, mode, rx), reader);
+            read.map_err(|e| anyhow::anyhow!("Task error: {}", e))?;
+            rows?
+        }
+    };
+    drop(file);
+
+    db_state.cache.lock().await.clear();
+
+    tracing::info!("Uploaded {} rows into {}.{}.{}", rows, database, schema, table);
+
+    let columns = db_state
+        .db
+        .get_record_batches(
+            &"SELECT column_name, data_type FROM duckdb_columns() \
+                WHERE database_name = current_database() AND schema_name = ? AND table_name = ? \
+                ORDER BY column_index"
+                .to_string(),
+            &Some(vec![SqlValue::Text(schema.clone()), SqlValue::Text(table.clone())]),
+            &None,
+            &None,
+            &None,
+            &None,
+            METADATA_ROW_LIMIT,
+            &None,
+            &None,
+            &None,
+            &cancel_token,
+        )
+        .await?;
+
+    let columns = columns.iter().map(upload_columns).collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(UploadInfo {
+        columns: columns.into_iter().flatten().collect(),
+        database,
+        schema,
+        table,
+        mode,
+        format,
+        rows,
+    })
+}
+
+/// Streams an upload body into a new file.
+async fn write_file<S, E>(path: &Path, mut body: S) -> Result<(), AppError>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: std::fmt::Display,
+{
+    let mut file = tokio::fs::File::create(path).await?;
+
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(|e| AppError::BadRequest(anyhow::anyhow!("Failed to read upload: {}", e).into()))?;
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+
+    Ok(())
+}
+
+type ArrowBatches = Box<dyn Iterator<Item = Result<RecordBatch, arrow::error::ArrowError>> + Send>;
+
+/// Opens an Arrow IPC upload, which may be in the file or the stream format, and reads its schema.
+fn open_arrow_file(path: &Path) -> anyhow::Result<(SchemaRef, ArrowBatches)> {
+    let mut file = File::open(path)?;
+    let mut magic = [0; ARROW_FILE_MAGIC.len()];
+    let is_file = file.read_exact(&mut magic).is_ok() && magic == ARROW_FILE_MAGIC;
+    file.seek(SeekFrom::Start(0))?;
+
+    if is_file {
+        let reader = FileReader::try_new(BufReader::new(file), None)?;
+        Ok((reader.schema(), Box::new(reader)))
+    }
+    else {
+        let reader = StreamReader::try_new(BufReader::new(file), None)?;
+        Ok((reader.schema(), Box::new(reader)))
+    }
+}
+
+/// Sends the record batches of an Arrow upload, headed by an empty batch carrying the schema,
+/// so that an upload without rows still creates its table.
+fn forward_arrow_batches(schema: SchemaRef, batches: ArrowBatches, tx: mpsc::Sender<anyhow::Result<RecordBatch>>) {
+    if tx.blocking_send(Ok(RecordBatch::new_empty(schema))).is_err() {
+        return;
+    }
+
+    for batch in batches {
+        let failed = batch.is_err();
+        let batch = batch.map_err(|e| anyhow::anyhow!("Failed to read Arrow upload: {}", e));
+        // A closed channel means the appender already failed and reports its own error
+        if tx.blocking_send(batch).is_err() || failed {
+            break;
+        }
+    }
+}
+
+fn upload_columns(batch: &RecordBatch) -> anyhow::Result<Vec<UploadColumn>> {
+    let string_column = |index: usize| {
+        batch
+            .column(index)
+            .as_string_opt::<i32>()
+            .ok_or_else(|| anyhow::anyhow!("Column listing has a {} column", batch.column(index).data_type()))
+    };
+    let names = string_column(0)?;
+    let types = string_column(1)?;
+
+    Ok(names
+        .iter()
+        .zip(types.iter())
+        .map(|(name, data_type)| UploadColumn {
+            name: name.unwrap_or_default().to_string(),
+            data_type: data_type.unwrap_or_default().to_string(),
+        })
+        .collect())
+}
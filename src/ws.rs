@@ -0,0 +1,199 @@
+use axum::{
+    body::Bytes,
+    extract::{
+        State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    response::Response,
+};
+use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::{sync::mpsc, task::JoinSet};
+use uuid::Uuid;
+
+use crate::{
+    constants::WS_CHANNEL_CAPACITY,
+    interfaces::{AppError, QueryParams, QueryResponse},
+    query,
+    state::AppState,
+};
+
+/// A text message from the client: either a query or the ID of a query to cancel.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum WsRequest {
+    Cancel { cancel: String },
+    Query(Box<QueryParams>),
+}
+
+pub async fn ws_handler(State(state): State<Arc<AppState>>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(move |socket| handle_socket(socket, state))
+}
+
+/// Runs every query sent over the socket concurrently. Results go out as soon as they are
+/// ready, so each frame carries the ID of the query it answers.
+async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
+    let (mut sink, mut messages) = socket.split();
+    let (tx, mut rx) = mpsc::channel::<Message>(WS_CHANNEL_CAPACITY);
+
+    let writer = tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            if sink.send(message).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut queries = JoinSet::new();
+
+    while let Some(Ok(message)) = messages.next().await {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        // Reap finished query tasks so a long-lived socket does not pile them up
+        while queries.try_join_next().is_some() {}
+
+        let state = Arc::clone(&state);
+        let tx = tx.clone();
+
+        match serde_json::from_str::<WsRequest>(text.as_str()) {
+            Ok(WsRequest::Cancel { cancel }) => {
+                let message = match query::cancel_query(&state, cancel.clone()).await {
+                    Ok(response) => response_message(response).await,
+                    Err(e) => error_message(Some(&cancel), &e),
+                };
+                let _ = tx.send(message).await;
+            }
+            Ok(WsRequest::Query(mut params)) => {
+                // Queries without an ID get one here, so that every frame answering them,
+                // errors included, tells the client which query it belongs to
+                let query_id = params.query_id.get_or_insert_with(|| Uuid::new_v4().to_string()).clone();
+
+                queries.spawn(async move {
+                    let response = query::with_db_retry(&state, &params, |state, params| {
+                        Box::pin(query::handle(state, params))
+                    })
+                    .await;
+
+                    let message = match response {
+                        Ok(response) => query_message(&query_id, response).await,
+                        Err(e) => error_message(Some(&query_id), &e),
+                    };
+                    let _ = tx.send(message).await;
+                });
+            }
+            Err(e) => {
+                let error = AppError::BadRequest(anyhow::anyhow!("Invalid message: {}", e).into());
+                let _ = tx.send(error_message(None, &error)).await;
+            }
+        }
+    }
+
+    // The client is gone, so whatever is still running gets cancelled
+    queries.shutdown().await;
+    drop(tx);
+    let _ = writer.await;
+}
+
+/// Turns the response of a query into a frame. JSON results and acknowledgements are text
+/// frames of the form `{"query_id": ..., "result": ...}`. Everything else is a binary frame
+/// holding the big-endian `u32` length of the query ID, the query ID and then the body.
+async fn query_message(query_id: &str, response: QueryResponse) -> Message {
+    let result = match response {
+        QueryResponse::QueryWithId { result, .. } => *result,
+        response => response,
+    };
+
+    // Pages of a paged query carry the cursor of the next page along in text frames
+    let (result, page) = match result {
+        QueryResponse::Page { cursor, has_more, result } => (*result, Some((cursor, has_more))),
+        result => (result, None),
+    };
+
+    let text = matches!(result, QueryResponse::Json(_) | QueryResponse::JsonStream(_) | QueryResponse::Empty);
+    let body = match result.into_body().await {
+        Ok(body) => body,
+        Err(e) => return error_message(Some(query_id), &e.into()),
+    };
+
+    if text {
+        Message::Text(text_frame(query_id, &body, page).into())
+    }
+    else {
+        Message::Binary(Bytes::from(binary_frame(query_id, &body)))
+    }
+}
+
+/// Turns the answer to a cancellation into a text frame.
+async fn response_message(response: QueryResponse) -> Message {
+    match response.into_body().await {
+        Ok(body) => Message::Text(String::from_utf8_lossy(&body).into_owned().into()),
+        Err(e) => error_message(None, &e.into()),
+    }
+}
+
+fn text_frame(query_id: &str, body: &[u8], page: Option<(Option<String>, bool)>) -> String {
+    let result = if body.is_empty() {
+        "null".to_string()
+    }
+    else {
+        String::from_utf8_lossy(body).into_owned()
+    };
+    let page = match page {
+        Some((cursor, has_more)) => format!(",\"cursor\":{},\"has_more\":{}", serde_json::Value::from(cursor), has_more),
+        None => String::new(),
+    };
+
+    format!("{{\"query_id\":{},\"result\":{}{}}}", serde_json::Value::from(query_id), result, page)
+}
+
+fn binary_frame(query_id: &str, body: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(4 + query_id.len() + body.len());
+    frame.extend_from_slice(&(query_id.len() as u32).to_be_bytes());
+    frame.extend_from_slice(query_id.as_bytes());
+    frame.extend_from_slice(body);
+    frame
+}
+
+fn error_message(query_id: Option<&str>, error: &AppError) -> Message {
+    Message::Text(
+        serde_json::json!({
+            "query_id": query_id,
+            "error": error.to_string(),
+        })
+        .to_string()
+        .into(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_frame() {
+        assert_eq!(text_frame("q1", b"[{\"a\":1}]", None), r#"{"query_id":"q1","result":[{"a":1}]}"#);
+        assert_eq!(text_frame("q\"2", b"", None), r#"{"query_id":"q\"2","result":null}"#);
+        assert_eq!(
+            text_frame("q3", b"[]", Some((Some("c1".to_string()), true))),
+            r#"{"query_id":"q3","result":[],"cursor":"c1","has_more":true}"#
+        );
+        assert_eq!(
+            text_frame("q4", b"[]", Some((None, false))),
+            r#"{"query_id":"q4","result":[],"cursor":null,"has_more":false}"#
+        );
+    }
+
+    #[test]
+    fn test_binary_frame() {
+        let frame = binary_frame("abc", &[1, 2, 3, 4]);
+        assert_eq!(&frame[..4], &3u32.to_be_bytes());
+        assert_eq!(&frame[4..7], b"abc");
+        assert_eq!(&frame[7..], &[1, 2, 3, 4]);
+        assert_eq!(binary_frame("", &[]), vec![0, 0, 0, 0]);
+    }
+}